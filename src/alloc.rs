@@ -69,16 +69,52 @@
 
 extern crate rand;
 
+use crate::sync::{arc_from_std, Arc, AtomicU64, AtomicUsize, Mutex, MutexGuard};
+use crate::{
+    AlignmentViolation, AllocEvent, AllocEventKind, AllocHistory, AllocStats, DropPolicy,
+    GenerationalTestAlloc, SubregionTestAlloc, TestAllocBuilder, Violation, ViolationPolicy,
+    ZeroSizePolicy,
+};
+#[cfg(feature = "serde")]
+use crate::{AllocationRecord, AllocationSnapshot};
+#[cfg(feature = "allocator_api")]
+use core::alloc::{AllocError, Allocator};
 use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+use rustc_hash::FxBuildHasher;
 use std::alloc::System;
-use std::collections::BTreeMap;
+#[cfg(feature = "backtraces")]
+use std::backtrace::Backtrace;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::hash::BuildHasher;
+use std::sync::atomic::Ordering;
+use std::sync::TryLockError;
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 /// Alias to `TestAlloc<System>` .
 /// 'GAlloc' stands for `Gharial Alloc`
 pub type GAlloc = TestAlloc<System>;
 
+/// Alias to `TestAlloc<A, FxBuildHasher>` .
+///
+/// The addresses of the pointers are well-distributed, so `FxHasher` is enough and faster than
+/// the default `SipHash` to track many allocations.
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{FastTestAlloc, TestBox};
+/// use std::alloc::System;
+///
+/// let alloc = FastTestAlloc::with_hasher(System);
+/// let _five = TestBox::new(5, alloc.clone());
+/// assert_eq!(1, alloc.allocation_count());
+/// ```
+pub type FastTestAlloc<A = System> = TestAlloc<A, FxBuildHasher>;
+
 /// `TestAlloc` is a implementation for `GlobalAlloc` to test memory leak and so on.
 ///
 /// It is a wrapper of another `GlobalAlloc`, and checks the requests delegating to the backend
@@ -95,21 +131,121 @@ pub type GAlloc = TestAlloc<System>;
 ///   (Note that cloned instances share the allocating memory information. The check is done when the
 ///   last cloned instance is dropped.)
 ///
+/// `TestAlloc` panics on a violation by default. See [`ViolationPolicy`] to record them instead.
+///
+/// `S` is the hasher of the tracking information. See also [`FastTestAlloc`] .
+///
 /// See also [`GAlloc`] . It is an alias to `TestAlloc<std::alloc::System>` .
-pub struct TestAlloc<A>
+pub struct TestAlloc<A, S = RandomState>
 where
     A: GlobalAlloc,
+    S: BuildHasher,
 {
     alloc: A,
-    allocatings: Arc<Mutex<BTreeMap<*mut u8, Layout>>>,
+    allocatings: Arc<Mutex<HashMap<Addr, Allocation, S>>>,
+    tagged_allocations: Arc<Mutex<HashMap<Addr, u64>>>,
+    violations: Arc<Mutex<Vec<Violation>>>,
+    violation_policy: ViolationPolicy,
+    zero_size_policy: ZeroSizePolicy,
+    drop_policy: DropPolicy,
+    max_live: Option<usize>,
+    max_alloc_size: Option<usize>,
+    max_alignment_warning: Option<usize>,
+    name: Option<Arc<String>>,
+    oom_handler: Option<Arc<dyn Fn(Layout) + Send + Sync>>,
+    dealloc_hook: Option<Arc<dyn Fn(*mut u8, Layout) + Send + Sync>>,
+    alloc_hook: Option<Arc<dyn Fn(Layout, *mut u8) + Send + Sync>>,
+    history: Arc<Mutex<VecDeque<AllocEvent>>>,
+    history_capacity: usize,
+    stats: Arc<Mutex<AllocStats>>,
+    size_histogram: Arc<Mutex<BTreeMap<usize, u64>>>,
+    #[cfg(feature = "backtraces")]
+    backtraces: bool,
+    alloc_ids: bool,
+    strict_provenance: bool,
+    next_alloc_id: Arc<AtomicU64>,
+    contention_count: Arc<AtomicU64>,
+    // The monotonic base of `last_alloc_time` , shared by the cloned instances.
+    created_at: Instant,
+    // Nanoseconds since `created_at` plus 1. 0 means no allocation has happened.
+    last_alloc_time: Arc<AtomicU64>,
+}
+
+/// The default number of the events that [`TestAlloc::history`] returns.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+
+/// The key of the tracking information.
+///
+/// It is the exposed address under feature `strict_provenance` , or the pointer itself otherwise.
+/// The pointer is reconstructed from the exposed provenance, so that it is still valid to
+/// deallocate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Addr(
+    #[cfg(not(feature = "strict_provenance"))] *mut u8,
+    #[cfg(feature = "strict_provenance")] usize,
+);
+
+impl From<*mut u8> for Addr {
+    fn from(ptr: *mut u8) -> Self {
+        #[cfg(not(feature = "strict_provenance"))]
+        {
+            Self(ptr)
+        }
+        #[cfg(feature = "strict_provenance")]
+        {
+            Self(ptr.expose_provenance())
+        }
+    }
+}
+
+impl Addr {
+    fn ptr(self) -> *mut u8 {
+        #[cfg(not(feature = "strict_provenance"))]
+        {
+            self.0
+        }
+        #[cfg(feature = "strict_provenance")]
+        {
+            core::ptr::with_exposed_provenance_mut(self.0)
+        }
+    }
 }
 
-impl<A> Default for TestAlloc<A>
+/// Information about an allocated pointer.
+#[derive(Clone, Debug)]
+struct Allocation {
+    layout: Layout,
+    allocated_at: Instant,
+    zeroed: bool,
+    id: Option<u64>,
+    thread: ThreadId,
+    annotation: Option<String>,
+    #[cfg(feature = "backtraces")]
+    backtrace: Option<std::sync::Arc<Backtrace>>,
+}
+
+impl From<Layout> for Allocation {
+    fn from(layout: Layout) -> Self {
+        Self {
+            layout,
+            allocated_at: Instant::now(),
+            zeroed: false,
+            id: None,
+            thread: std::thread::current().id(),
+            annotation: None,
+            #[cfg(feature = "backtraces")]
+            backtrace: None,
+        }
+    }
+}
+
+impl<A, S> Default for TestAlloc<A, S>
 where
     A: GlobalAlloc + Default,
+    S: BuildHasher + Default,
 {
     fn default() -> Self {
-        Self::from(A::default())
+        Self::with_hasher(A::default())
     }
 }
 
@@ -118,75 +254,226 @@ where
     A: GlobalAlloc,
 {
     fn from(inner: A) -> Self {
-        Self {
-            alloc: inner,
-            allocatings: Arc::default(),
-        }
+        Self::with_capacity(inner, 0)
     }
 }
 
-impl<A> Clone for TestAlloc<A>
+impl<A, S> Clone for TestAlloc<A, S>
 where
     A: GlobalAlloc + Clone,
+    S: BuildHasher,
 {
     fn clone(&self) -> Self {
         Self {
             alloc: self.alloc.clone(),
             allocatings: self.allocatings.clone(),
+            tagged_allocations: self.tagged_allocations.clone(),
+            violations: self.violations.clone(),
+            violation_policy: self.violation_policy,
+            zero_size_policy: self.zero_size_policy,
+            drop_policy: self.drop_policy,
+            max_live: self.max_live,
+            max_alloc_size: self.max_alloc_size,
+            max_alignment_warning: self.max_alignment_warning,
+            name: self.name.clone(),
+            oom_handler: self.oom_handler.clone(),
+            dealloc_hook: self.dealloc_hook.clone(),
+            alloc_hook: self.alloc_hook.clone(),
+            history: self.history.clone(),
+            history_capacity: self.history_capacity,
+            stats: self.stats.clone(),
+            size_histogram: self.size_histogram.clone(),
+            #[cfg(feature = "backtraces")]
+            backtraces: self.backtraces,
+            alloc_ids: self.alloc_ids,
+            strict_provenance: self.strict_provenance,
+            next_alloc_id: self.next_alloc_id.clone(),
+            contention_count: self.contention_count.clone(),
+            created_at: self.created_at,
+            last_alloc_time: self.last_alloc_time.clone(),
         }
     }
 }
 
-impl<A> Drop for TestAlloc<A>
+impl<A, S> Drop for TestAlloc<A, S>
 where
     A: GlobalAlloc,
+    S: BuildHasher,
 {
     fn drop(&mut self) {
-        if Arc::strong_count(&self.allocatings) == 1 {
-            let allocatings = self.allocatings.lock().unwrap();
-            if allocatings.is_empty() == false {
-                let message0 = "Memory leak is detected";
-                let message1 =
-                    "The allocator is dropped before the allocated pointer is deallocated";
-                panic!("{}: {}", message0, message1);
+        if Arc::strong_count(&self.allocatings) != 1 {
+            return;
+        }
+
+        if self.drop_policy != DropPolicy::IgnoreOnLeak {
+            #[cfg(feature = "backtraces")]
+            self.print_leak_backtraces();
+        }
+
+        // Panicking here during unwinding would abort the process and hide the original panic
+        // message.
+        let panicking = std::thread::panicking();
+        let leaks = self.providing_pointers();
+        let label = format!("{}{} live allocations: ", self.label(), leaks.len());
+        for (ptr, layout) in leaks {
+            let violation = Violation::Leak { ptr, layout };
+            let annotation = match self.lock_allocatings().get(&Addr::from(ptr)) {
+                Some(Allocation {
+                    annotation: Some(annotation),
+                    ..
+                }) => format!(" [{:?}]", annotation),
+                _ => String::new(),
+            };
+            match self.drop_policy {
+                DropPolicy::PanicOnLeak
+                    if !panicking && self.violation_policy == ViolationPolicy::Panic =>
+                {
+                    panic!("{}{}{}", label, violation, annotation)
+                }
+                DropPolicy::PanicOnLeak if !panicking => self.violate(violation),
+                DropPolicy::PanicOnLeak | DropPolicy::PrintOnLeak => {
+                    eprintln!("{}{}{}", label, violation, annotation)
+                }
+                DropPolicy::IgnoreOnLeak => {}
+            }
+        }
+
+        if self.violation_policy == ViolationPolicy::Record {
+            for violation in self.lock(&self.violations).iter() {
+                eprintln!("{}{}", self.label(), violation);
             }
         }
     }
 }
 
-unsafe impl<A> GlobalAlloc for TestAlloc<A>
+unsafe impl<A, S> GlobalAlloc for TestAlloc<A, S>
 where
     A: GlobalAlloc,
+    S: BuildHasher,
 {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let ptr = self.alloc.alloc(layout);
+        self.check_layout(layout);
+        let ptr = if self.exceeds_max_alloc_size(layout.size()) {
+            core::ptr::null_mut()
+        } else {
+            self.alloc.alloc(layout)
+        };
+        self.track_alloc(ptr, layout, false);
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.check_layout(layout);
+        let ptr = if self.exceeds_max_alloc_size(layout.size()) {
+            core::ptr::null_mut()
+        } else {
+            self.alloc.alloc_zeroed(layout)
+        };
+
+        #[cfg(feature = "verify")]
         if !ptr.is_null() {
-            let mut allocatings = self.allocatings.lock().unwrap();
-            let prev = allocatings.insert(ptr, layout);
-            assert_eq!(true, prev.is_none());
+            let bytes = core::slice::from_raw_parts(ptr, layout.size());
+            assert!(
+                bytes.iter().all(|&b| b == 0),
+                "GlobalAlloc.alloc_zeroed() returned memory which is not zeroed."
+            );
         }
 
+        self.track_alloc(ptr, layout, true);
         ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         // `GlobalAlloc::dealloc` interface does not define the behavior when ptr is null.
         if ptr.is_null() {
-            panic!("Null pointer is passed to method GlobalAlloc.dealloc().");
+            self.violate(Violation::NullDealloc { layout });
+            return;
         }
 
-        // Enclose to release the lock as soon as possible.
-        {
-            let mut allocatings = self.allocatings.lock().unwrap();
-            let prev = allocatings.remove(&ptr).unwrap();
-            if layout != prev {
-                panic!(
-                    "GlobalAlloc.dealloc() is passed a different layout from GlobalAlloc.alloc()"
-                );
+        if let Some(hook) = self.dealloc_hook.as_ref() {
+            hook(ptr, layout);
+        }
+
+        let prev = self.lock_allocatings().remove(&Addr::from(ptr));
+        self.lock(&self.tagged_allocations).remove(&Addr::from(ptr));
+        match prev {
+            None => self.violate(Violation::UnknownDealloc { ptr, layout }),
+            Some(Allocation {
+                layout: alloc_layout,
+                ..
+            }) => {
+                if layout != alloc_layout {
+                    self.violate(Violation::LayoutMismatch {
+                        ptr,
+                        alloc_layout,
+                        dealloc_layout: layout,
+                    });
+                }
+
+                self.lock(&self.stats).on_dealloc(alloc_layout.size());
+
+                // Pass the layout used on the allocation in case of `ViolationPolicy::Record` .
+                self.record_event(AllocEventKind::Dealloc, ptr, alloc_layout);
+                self.alloc.dealloc(ptr, alloc_layout);
+
+                #[cfg(debug_assertions)]
+                self.assert_stable_entry(ptr, alloc_layout);
             }
         }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        self.check_layout(new_layout);
+        if self.exceeds_max_alloc_size(new_size) {
+            self.track_alloc(core::ptr::null_mut(), new_layout, false);
+            return core::ptr::null_mut();
+        }
 
-        self.alloc.dealloc(ptr, layout);
+        // Stop tracking `ptr` before the backend allocator frees it; another thread can be given
+        // the same address at once.
+        let prev = self.lock_allocatings().remove(&Addr::from(ptr));
+        let prev = match prev {
+            None => {
+                self.violate(Violation::UnknownDealloc { ptr, layout });
+                return core::ptr::null_mut();
+            }
+            Some(prev) => prev,
+        };
+        if layout != prev.layout {
+            let alloc_layout = prev.layout;
+            self.lock_allocatings().insert(Addr::from(ptr), prev);
+            self.violate(Violation::LayoutMismatch {
+                ptr,
+                alloc_layout,
+                dealloc_layout: layout,
+            });
+            return core::ptr::null_mut();
+        }
+
+        let new_ptr = self.alloc.realloc(ptr, layout, new_size);
+        let mut annotation = None;
+        if new_ptr.is_null() {
+            // `ptr` is still valid.
+            self.lock_allocatings().insert(Addr::from(ptr), prev);
+        } else {
+            annotation = prev.annotation;
+            self.lock(&self.stats).on_dealloc(layout.size());
+            self.record_event(AllocEventKind::Dealloc, ptr, layout);
+
+            let mut tagged_allocations = self.lock(&self.tagged_allocations);
+            if let Some(tag) = tagged_allocations.remove(&Addr::from(ptr)) {
+                tagged_allocations.insert(Addr::from(new_ptr), tag);
+            }
+        }
+
+        self.track_alloc(new_ptr, new_layout, false);
+        if let Some(annotation) = annotation {
+            if let Some(a) = self.lock_allocatings().get_mut(&Addr::from(new_ptr)) {
+                a.annotation = Some(annotation);
+            }
+        }
+        new_ptr
     }
 }
 
@@ -194,118 +481,2606 @@ impl<A> TestAlloc<A>
 where
     A: GlobalAlloc,
 {
-    /// Returns the list of pointers and layouts that were allocated and not deallocated.
-    /// The returned value is sorted by the pointer.
-    pub fn providing_pointers(&self) -> Vec<(*mut u8, Layout)> {
-        self.allocatings
-            .lock()
-            .unwrap()
-            .iter()
-            .map(|(&k, &v)| (k, v))
-            .collect()
+    /// Creates a new instance with `inner` as the backend allocator.
+    ///
+    /// The tracking information is pre-allocated to hold at least `capacity` pointers, so that
+    /// the first `capacity` allocations do not make `TestAlloc` itself allocate more memory.
+    pub fn with_capacity(inner: A, capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(inner, capacity, RandomState::default())
+    }
+
+    /// Returns a builder to create a new instance with `inner` as the backend allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{TestAlloc, ViolationPolicy, ZeroSizePolicy};
+    /// use std::alloc::System;
+    ///
+    /// let alloc = TestAlloc::builder(System)
+    ///     .capacity(16)
+    ///     .violation_policy(ViolationPolicy::Record)
+    ///     .zero_size_policy(ZeroSizePolicy::Violation)
+    ///     .build();
+    /// ```
+    pub fn builder(inner: A) -> TestAllocBuilder<A> {
+        TestAllocBuilder::from(inner)
     }
 }
 
-impl<A> fmt::Debug for TestAlloc<A>
+impl<A, S> TestAlloc<A, S>
 where
-    A: GlobalAlloc + fmt::Debug,
+    A: GlobalAlloc,
+    S: BuildHasher,
 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TestAlloc")
-            .field("alloc", &self.alloc)
-            .field("info", &format!("{:p}", self.allocatings))
-            .finish()
+    /// Creates a new instance with `inner` as the backend allocator and `S::default()` as the
+    /// hasher of the tracking information.
+    pub fn with_hasher(inner: A) -> Self
+    where
+        S: Default,
+    {
+        Self::with_capacity_and_hasher(inner, 0, S::default())
     }
-}
 
-// `Send` is not implemented automatically because the key type of the `allocating` (*mut u8)
-// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
-// It is safe to implement `Send` manually.
-unsafe impl<A> Send for TestAlloc<A> where A: GlobalAlloc + Send {}
+    /// Creates a new instance with `inner` as the backend allocator and `hasher` as the hasher
+    /// of the tracking information.
+    ///
+    /// See also [`with_capacity`] .
+    ///
+    /// [`with_capacity`]: TestAlloc::with_capacity
+    // The keys of the map are never dereferenced. See the comment on the `Send` implementation.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn with_capacity_and_hasher(inner: A, capacity: usize, hasher: S) -> Self {
+        Self {
+            alloc: inner,
+            allocatings: Arc::new(Mutex::new(HashMap::with_capacity_and_hasher(
+                capacity, hasher,
+            ))),
+            tagged_allocations: Arc::default(),
+            violations: Arc::default(),
+            violation_policy: ViolationPolicy::default(),
+            zero_size_policy: ZeroSizePolicy::default(),
+            drop_policy: DropPolicy::default(),
+            max_live: None,
+            max_alloc_size: None,
+            max_alignment_warning: None,
+            name: None,
+            oom_handler: None,
+            dealloc_hook: None,
+            alloc_hook: None,
+            history: Arc::default(),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            stats: Arc::default(),
+            size_histogram: Arc::default(),
+            #[cfg(feature = "backtraces")]
+            backtraces: false,
+            alloc_ids: false,
+            strict_provenance: cfg!(feature = "strict_provenance"),
+            next_alloc_id: Arc::default(),
+            contention_count: Arc::default(),
+            created_at: Instant::now(),
+            last_alloc_time: Arc::default(),
+        }
+    }
 
-// `Send` is not implemented automatically because the key type of the `allocating` (*mut u8)
-// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
-// It is safe to implement `Send` manually.
-unsafe impl<A> Sync for TestAlloc<A> where A: GlobalAlloc + Send + Sync {}
+    /// Wraps `self` in `Arc` .
+    ///
+    /// Unlike `clone` , it does not clone the backend allocator. `&TestAlloc` implements
+    /// `GlobalAlloc` , so each thread can use `&*arc` as the allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::thread;
+    ///
+    /// let alloc = GAlloc::default().into_arc();
+    ///
+    /// let handles: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         let alloc = alloc.clone();
+    ///         thread::spawn(move || {
+    ///             let _box = TestBox::new(i, &*alloc);
+    ///             assert!(1 <= alloc.allocation_count());
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// for h in handles {
+    ///     h.join().unwrap();
+    /// }
+    /// assert_eq!(0, alloc.allocation_count());
+    /// ```
+    pub fn into_arc(self) -> Arc<Self> {
+        Arc::new(self)
+    }
 
-/// `NeverAlloc` is an implementation for `GlobalAlloc` , which always fails.
-/// For example, `NeverAlloc::alloc` always returns a null pointer.
-#[derive(Clone, Copy, Debug)]
-pub struct NeverAlloc;
+    /// Unwraps `arc` if it is the only strong reference, or returns `arc` itself.
+    pub fn try_from_arc(arc: Arc<Self>) -> Result<Self, Arc<Self>> {
+        Arc::try_unwrap(arc)
+    }
 
-impl Default for NeverAlloc {
-    fn default() -> Self {
-        Self
+    /// Returns true if `a` and `b` share the tracking information, i.e. one is cloned from the
+    /// other, or false.
+    ///
+    /// `TestAlloc` implements `PartialEq` and `Eq` in the same way.
+    pub fn same_allocator(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.allocatings, &b.allocatings)
     }
-}
 
-unsafe impl GlobalAlloc for NeverAlloc {
-    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
-        core::ptr::null_mut()
+    /// Creates [`SubregionTestAlloc`] whose parent is `self` .
+    ///
+    /// Pass a clone to keep using `self` ; e.g. `alloc.clone().subregion()` .
+    pub fn subregion(self) -> SubregionTestAlloc<A, S> {
+        SubregionTestAlloc::from(self)
     }
 
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
-        panic!("Method NeverAlloc.dealloc() is called.");
+    /// Creates [`GenerationalTestAlloc`] wrapping `self` to detect use-after-free.
+    pub fn with_generation_counter(self) -> GenerationalTestAlloc<A, S> {
+        GenerationalTestAlloc::from(self)
     }
-}
 
-/// `MaybeAlloc` is an implementation for `GlobalAlloc` , which occasionally fails to allocate.
-///
-/// It is a wrapper of another `GlobalAlloc` , and delegates the requests to the inner, however, sometimes fails to allocate
-/// memory on purpose. i.e. `MaybeAlloc::alloc` can return null pointer before memory exhaustion.
-///
-/// The failure properbility is 1/16.
-#[derive(Debug)]
-pub struct MaybeAlloc<A = TestAlloc<System>>
-where
-    A: GlobalAlloc,
-{
-    alloc: A,
-}
+    /// Provides a reference to the backend allocator.
+    pub(crate) fn inner(&self) -> &A {
+        &self.alloc
+    }
 
-impl<A> Default for MaybeAlloc<A>
-where
-    A: GlobalAlloc + Default,
-{
-    fn default() -> Self {
-        Self::from(A::default())
+    /// Creates a new instance with the clone of the backend allocator and the same configuration
+    /// as `self` .
+    ///
+    /// Unlike `clone` , the new instance does not share the tracking information with `self` ;
+    /// e.g. the leak check is done independently.
+    pub fn clone_unshared(&self) -> Self
+    where
+        A: Clone,
+        S: Clone,
+    {
+        let hasher = self.lock_allocatings().hasher().clone();
+        let mut ret = Self::with_capacity_and_hasher(self.alloc.clone(), 0, hasher);
+        ret.violation_policy = self.violation_policy;
+        ret.zero_size_policy = self.zero_size_policy;
+        ret.drop_policy = self.drop_policy;
+        ret.max_live = self.max_live;
+        ret.max_alloc_size = self.max_alloc_size;
+        ret.max_alignment_warning = self.max_alignment_warning;
+        ret.name = self.name.clone();
+        ret.oom_handler = self.oom_handler.clone();
+        ret.dealloc_hook = self.dealloc_hook.clone();
+        ret.alloc_hook = self.alloc_hook.clone();
+        ret.history_capacity = self.history_capacity;
+        ret.alloc_ids = self.alloc_ids;
+        ret.strict_provenance = self.strict_provenance;
+        #[cfg(feature = "backtraces")]
+        {
+            ret.backtraces = self.backtraces;
+        }
+        ret
     }
-}
 
-impl<A> From<A> for MaybeAlloc<A>
-where
-    A: GlobalAlloc,
-{
-    fn from(alloc: A) -> Self {
-        Self { alloc }
+    /// Stops tracking all the pointers being allocated, and returns them.
+    ///
+    /// The caller takes the responsibility to deallocate them via the backend allocator; `self`
+    /// does not treat them as leaks any more.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another clone shares the tracking information with `self` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::GAlloc;
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    ///
+    /// let mut alloc = GAlloc::default();
+    /// let layout = Layout::new::<u64>();
+    /// let ptr = unsafe { alloc.alloc(layout) };
+    ///
+    /// let allocatings = alloc.take_allocatings();
+    /// assert_eq!(Some(&layout), allocatings.get(&ptr));
+    /// assert_eq!(0, alloc.allocation_count());
+    ///
+    /// unsafe { System.dealloc(ptr, layout) };
+    /// ```
+    pub fn take_allocatings(&mut self) -> HashMap<*mut u8, Layout> {
+        assert!(
+            !self.is_shared(),
+            "TestAlloc.take_allocatings() is called while the tracking information is shared."
+        );
+
+        let allocatings: HashMap<*mut u8, Layout> = self
+            .lock_allocatings()
+            .drain()
+            .map(|(addr, a)| (addr.ptr(), a.layout))
+            .collect();
+
+        let mut stats = self.lock(&self.stats);
+        let mut tagged_allocations = self.lock(&self.tagged_allocations);
+        for (ptr, layout) in allocatings.iter() {
+            stats.on_forget(layout.size());
+            tagged_allocations.remove(&Addr::from(*ptr));
+        }
+
+        allocatings
     }
-}
 
-impl<A> Clone for MaybeAlloc<A>
-where
-    A: GlobalAlloc + Clone,
-{
-    fn clone(&self) -> Self {
-        Self::from(self.alloc.clone())
+    /// Returns true if another clone shares the tracking information with `self` , or false.
+    ///
+    /// Note that `Arc` made by [`into_arc`] is not counted.
+    ///
+    /// [`into_arc`]: Self::into_arc
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.allocatings) > 1
     }
-}
 
-unsafe impl<A> GlobalAlloc for MaybeAlloc<A>
-where
-    A: GlobalAlloc,
-{
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if rand::random::<u8>() % 16 == 0 {
-            core::ptr::null_mut()
+    /// Panics if a pointer is being provided by both `a` and `b` .
+    ///
+    /// It detects that the same pointer is registered to two allocators, which leads to
+    /// double free.
+    pub fn assert_disjoint<B, T>(a: &Self, b: &TestAlloc<B, T>)
+    where
+        B: GlobalAlloc,
+        T: BuildHasher,
+    {
+        let b_ptrs: HashMap<*mut u8, Layout> = b.providing_pointers().into_iter().collect();
+        let overlaps: Vec<*mut u8> = a
+            .providing_pointers()
+            .into_iter()
+            .map(|(ptr, _)| ptr)
+            .filter(|ptr| b_ptrs.contains_key(ptr))
+            .collect();
+
+        assert!(
+            overlaps.is_empty(),
+            "The allocators are providing the same pointers: {:?}",
+            overlaps
+        );
+    }
+
+    /// Sets the behavior on a violation and returns `self` .
+    pub fn with_violation_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.violation_policy = policy;
+        self
+    }
+
+    /// Sets the behavior on a zero-size allocation request and returns `self` .
+    pub fn with_zero_size_policy(mut self, policy: ZeroSizePolicy) -> Self {
+        self.zero_size_policy = policy;
+        self
+    }
+
+    /// Sets the behavior on memory leak detected on the drop and returns `self` .
+    ///
+    /// It is useful if some allocations are leaked on purpose.
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Sets the name of `self` and returns `self` .
+    ///
+    /// The name is included in the messages on violations; e.g. the leak check on the drop
+    /// panics with "TestAlloc 'name': 3 live allocations: ...". It helps to identify which
+    /// allocator leaks when a test uses several allocators.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(Arc::new(name.into()));
+        self
+    }
+
+    /// Makes `self` panic immediately when the number of the live allocations exceeds `n` , and
+    /// returns `self` .
+    ///
+    /// Unlike the leak check on the drop, it shows where the extra allocation is done.
+    pub fn with_max_live(mut self, n: usize) -> Self {
+        self.max_live = Some(n);
+        self
+    }
+
+    /// Makes `self` treat an allocation request whose alignment is greater than `align` as
+    /// [`Violation::OverAligned`] , and returns `self` .
+    ///
+    /// It detects a container requesting over-aligned memory by accident. Use
+    /// `ViolationPolicy::Record` to print the violations as warnings rather than to panic.
+    pub fn with_max_alignment_warning(mut self, align: usize) -> Self {
+        self.max_alignment_warning = Some(align);
+        self
+    }
+
+    fn check_layout(&self, layout: Layout) {
+        if layout.size() == 0 && self.zero_size_policy == ZeroSizePolicy::Violation {
+            self.violate(Violation::ZeroSizeAlloc { layout });
+        }
+
+        if let Some(max_align) = self.max_alignment_warning {
+            if max_align < layout.align() {
+                self.violate(Violation::OverAligned { layout, max_align });
+            }
+        }
+    }
+
+    /// Makes `self` fail every allocation larger than `max_bytes` , and returns `self` .
+    ///
+    /// The failed request is not delegated to the backend allocator; the null pointer is
+    /// returned and the out-of-memory handler is called as if the backend allocator failed. It
+    /// simulates a system which limits the size of a single object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default().with_max_alloc_size(8);
+    /// assert!(TestBox::try_new(0_u64, alloc.clone()).is_ok());
+    /// assert!(TestBox::try_new([0_u64; 2], alloc.clone()).is_err());
+    /// ```
+    pub fn with_max_alloc_size(mut self, max_bytes: usize) -> Self {
+        self.max_alloc_size = Some(max_bytes);
+        self
+    }
+
+    fn exceeds_max_alloc_size(&self, size: usize) -> bool {
+        self.max_alloc_size.is_some_and(|max| max < size)
+    }
+
+    /// Registers `f` as the out-of-memory handler, and returns `self` .
+    ///
+    /// `f` is called with the requested layout every time the backend allocator returns a null
+    /// pointer. It can log the failure, capture a backtrace, or panic. Unless `f` panics, the
+    /// null pointer is still returned to the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{NeverAlloc, TestAlloc, TestBox};
+    /// use std::panic::{catch_unwind, AssertUnwindSafe};
+    ///
+    /// let alloc = TestAlloc::from(NeverAlloc).with_oom_handler(|layout| {
+    ///     panic!("Failed to allocate {} bytes", layout.size());
+    /// });
+    ///
+    /// let result = catch_unwind(AssertUnwindSafe(|| TestBox::try_new(5_u32, &alloc).ok()));
+    /// assert!(result.is_err());
+    /// ```
+    pub fn with_oom_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Layout) + Send + Sync + 'static,
+    {
+        let f: std::sync::Arc<dyn Fn(Layout) + Send + Sync> = std::sync::Arc::new(f);
+        self.oom_handler = Some(arc_from_std(f));
+        self
+    }
+
+    /// Registers `hook` to be called with the layout and the returned pointer after every
+    /// successful allocation, and returns `self` .
+    ///
+    /// `hook` can close over a clone of `self` to inspect the tracking information; the pointer
+    /// is already tracked when `hook` is called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let sizes = Arc::new(Mutex::new(Vec::new()));
+    /// let alloc = {
+    ///     let sizes = sizes.clone();
+    ///     GAlloc::default().with_alloc_hook(move |layout, ptr| {
+    ///         assert!(!ptr.is_null());
+    ///         sizes.lock().unwrap().push(layout.size());
+    ///     })
+    /// };
+    ///
+    /// let _a = TestBox::new(0_u8, alloc.clone());
+    /// let _b = TestBox::new(0_u64, alloc.clone());
+    /// assert_eq!(vec![1, 8], *sizes.lock().unwrap());
+    /// ```
+    pub fn with_alloc_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Layout, *mut u8) + Send + Sync + 'static,
+    {
+        let hook: std::sync::Arc<dyn Fn(Layout, *mut u8) + Send + Sync> = std::sync::Arc::new(hook);
+        self.alloc_hook = Some(arc_from_std(hook));
+        self
+    }
+
+    /// Registers `hook` to be called with the pointer and the layout before every deallocation,
+    /// and returns `self` .
+    ///
+    /// The memory is still readable when `hook` is called. `hook` must not modify it.
+    ///
+    /// `realloc` does not call `hook` because the memory is moved rather than freed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let freed = Arc::new(Mutex::new(Vec::new()));
+    /// let alloc = {
+    ///     let freed = freed.clone();
+    ///     GAlloc::default().with_dealloc_hook(move |ptr, _layout| {
+    ///         let val = unsafe { *(ptr as *const u32) };
+    ///         freed.lock().unwrap().push(val);
+    ///     })
+    /// };
+    ///
+    /// drop(TestBox::new(5_u32, alloc.clone()));
+    /// drop(TestBox::new(7_u32, alloc.clone()));
+    /// assert_eq!(vec![5, 7], *freed.lock().unwrap());
+    /// ```
+    pub fn with_dealloc_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(*mut u8, Layout) + Send + Sync + 'static,
+    {
+        let hook: std::sync::Arc<dyn Fn(*mut u8, Layout) + Send + Sync> = std::sync::Arc::new(hook);
+        self.dealloc_hook = Some(arc_from_std(hook));
+        self
+    }
+
+    /// Sets the number of the events that [`history`] returns, and returns `self` .
+    ///
+    /// The default is [`DEFAULT_HISTORY_CAPACITY`] .
+    ///
+    /// [`history`]: Self::history
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history_capacity = capacity;
+        {
+            let mut history = self.lock(&self.history);
+            while capacity < history.len() {
+                history.pop_front();
+            }
+        }
+        self
+    }
+
+    /// Returns the snapshot of the recent allocations and deallocations.
+    pub fn history(&self) -> AllocHistory {
+        let history = self.lock(&self.history);
+        AllocHistory::from(history.iter().copied().collect::<Vec<_>>())
+    }
+
+    /// Asserts that the layouts of the most recent `expected.len()` allocations equal to
+    /// `expected` in chronological order.
+    ///
+    /// The order is taken from [`history`] , so `expected` must not be longer than the history
+    /// capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the actual layouts if the assertion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::Layout;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let _meta = TestBox::new(0_u8, alloc.clone());
+    /// let _data = TestBox::new([0_u64; 4], alloc.clone());
+    ///
+    /// alloc.assert_allocation_order(&[Layout::new::<u8>(), Layout::new::<[u64; 4]>()]);
+    /// ```
+    ///
+    /// [`history`]: Self::history
+    pub fn assert_allocation_order(&self, expected: &[Layout]) {
+        let mut actual: Vec<Layout> = self
+            .history()
+            .iter()
+            .rev()
+            .filter(|e| e.kind == AllocEventKind::Alloc)
+            .take(expected.len())
+            .map(|e| e.layout)
+            .collect();
+        actual.reverse();
+
+        if actual != expected {
+            panic!(
+                "{}The recent allocations are expected to be {:?}, but {:?}.",
+                self.label(),
+                expected,
+                actual
+            );
+        }
+    }
+
+    fn record_event(&self, kind: AllocEventKind, ptr: *mut u8, layout: Layout) {
+        if self.history_capacity == 0 {
+            return;
+        }
+
+        let mut history = self.lock(&self.history);
+        while self.history_capacity <= history.len() {
+            history.pop_front();
+        }
+        history.push_back(AllocEvent {
+            kind,
+            ptr: self.addr_of(ptr),
+            layout,
+            timestamp: Instant::now(),
+        });
+    }
+
+    /// Enables to capture the backtrace on every allocation, and returns `self` .
+    ///
+    /// The backtraces of the leaked pointers are printed to the stderr when the leak is detected
+    /// on the drop.
+    ///
+    /// The backtrace is captured by `std::backtrace::Backtrace::capture` , so environment
+    /// variable `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE` must be set to enable it.
+    #[cfg(feature = "backtraces")]
+    pub fn with_backtraces(mut self) -> Self {
+        self.backtraces = true;
+        self
+    }
+
+    #[cfg(feature = "backtraces")]
+    fn print_leak_backtraces(&self) {
+        let allocatings = self.lock_allocatings();
+        for (addr, allocation) in allocatings.iter() {
+            if let Some(backtrace) = allocation.backtrace.as_ref() {
+                eprintln!(
+                    "{:p} ({:?}) is leaked. It is allocated at:\n{}",
+                    addr.ptr(),
+                    allocation.layout,
+                    backtrace
+                );
+            }
+        }
+    }
+
+    // Registers `ptr` returned from the backend allocator.
+    fn track_alloc(&self, ptr: *mut u8, layout: Layout, zeroed: bool) {
+        if ptr.is_null() {
+            if let Some(f) = self.oom_handler.as_ref() {
+                f(layout);
+            }
         } else {
-            self.alloc.alloc(layout)
+            let mut allocation = self.new_allocation(layout);
+            allocation.zeroed = zeroed;
+            let (prev, live) = {
+                let mut allocatings = self.lock_allocatings();
+                (
+                    allocatings.insert(Addr::from(ptr), allocation),
+                    allocatings.len(),
+                )
+            };
+            match prev {
+                None => self.lock(&self.stats).on_alloc(layout.size()),
+                Some(_) => self.violate(Violation::DuplicatedAlloc { ptr, layout }),
+            }
+            *self
+                .lock(&self.size_histogram)
+                .entry(layout.size())
+                .or_insert(0) += 1;
+            self.record_event(AllocEventKind::Alloc, ptr, layout);
+            self.last_alloc_time
+                .store(self.nanos_since_creation() + 1, Ordering::Relaxed);
+
+            if let Some(hook) = self.alloc_hook.as_ref() {
+                hook(layout, ptr);
+            }
+
+            if let Some(n) = self.max_live {
+                if n < live {
+                    panic!("TestAlloc: live allocation count exceeded limit {}", n);
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            self.assert_stable_entry(ptr, layout);
         }
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    /// Asserts the invariants of the tracking information.
+    ///
+    /// Every tracked pointer must be non-null, and every tracked layout must have a power of 2
+    /// alignment and a non-zero size unless zero-size allocation is allowed. A failure indicates
+    /// a bug of `TestAlloc` itself.
+    ///
+    /// It scans all the tracked pointers. Debug builds check only the pointer allocated or
+    /// deallocated on every allocation and deallocation instead, not to make a test with many
+    /// allocations quadratic.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the violated invariant if any.
+    pub fn assert_stable(&self) {
+        // Panics after releasing the lock not to poison it.
+        let broken = self
+            .lock_allocatings()
+            .iter()
+            .find_map(|(addr, a)| self.broken_invariant(addr.ptr(), a.layout));
+
+        if let Some(msg) = broken {
+            panic!("{}invariant violated: {}.", self.label(), msg);
+        }
+    }
+
+    // Asserts the invariants of the tracking information only for `ptr` .
+    #[cfg(debug_assertions)]
+    fn assert_stable_entry(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(msg) = self.broken_invariant(ptr, layout) {
+            panic!("{}invariant violated: {}.", self.label(), msg);
+        }
+    }
+
+    fn broken_invariant(&self, ptr: *mut u8, layout: Layout) -> Option<String> {
+        // Zero-size allocations are tracked unless `check_layout` panics.
+        let zero_size_allowed = self.zero_size_policy == ZeroSizePolicy::Allow
+            || self.violation_policy == ViolationPolicy::Record;
+
         if ptr.is_null() {
-            panic!("Null pointer is passed to method GlobalAlloc.dealloc().");
+            Some("null pointer is tracked".to_string())
+        } else if !layout.align().is_power_of_two() {
+            Some(format!(
+                "{:p} has non power of 2 alignment {}",
+                ptr,
+                layout.align()
+            ))
+        } else if !zero_size_allowed && layout.size() == 0 {
+            Some(format!("{:p} has zero size", ptr))
+        } else {
+            None
         }
-        self.alloc.dealloc(ptr, layout);
+    }
+
+    fn new_allocation(&self, layout: Layout) -> Allocation {
+        let mut allocation = Allocation::from(layout);
+
+        #[cfg(feature = "backtraces")]
+        if self.backtraces {
+            allocation.backtrace = Some(std::sync::Arc::new(Backtrace::capture()));
+            allocation.id = Some(self.next_alloc_id.fetch_add(1, Ordering::Relaxed));
+        }
+
+        if self.alloc_ids && allocation.id.is_none() {
+            allocation.id = Some(self.next_alloc_id.fetch_add(1, Ordering::Relaxed));
+        }
+
+        allocation
+    }
+
+    /// Enables to assign a monotonically increasing ID to every allocation, and returns `self` .
+    ///
+    /// The IDs are assigned when `with_backtraces` is enabled as well.
+    /// See also [`allocation_id_of`] .
+    ///
+    /// [`allocation_id_of`]: Self::allocation_id_of
+    pub fn with_alloc_ids(mut self) -> Self {
+        self.alloc_ids = true;
+        self
+    }
+
+    /// Enables the strict provenance mode, and returns `self` .
+    ///
+    /// In this mode, `self` takes the address of a pointer via `pointer::addr` , which does not
+    /// expose the provenance, wherever it reports an address; e.g. [`history`] and
+    /// [`alloc_sorted_addresses`] . The tracking information is always compared by the address.
+    ///
+    /// It is enabled by default under feature `strict_provenance` , which also makes `self`
+    /// store the addresses instead of the pointers.
+    ///
+    /// [`history`]: Self::history
+    /// [`alloc_sorted_addresses`]: Self::alloc_sorted_addresses
+    pub fn with_strict_provenance(mut self) -> Self {
+        self.strict_provenance = true;
+        self
+    }
+
+    // Returns the address of `ptr` not to expose the provenance in the strict provenance mode.
+    fn addr_of(&self, ptr: *mut u8) -> usize {
+        if self.strict_provenance {
+            ptr.addr()
+        } else {
+            ptr as usize
+        }
+    }
+
+    /// Returns the ID assigned to `ptr` , or `None` if `ptr` is not being allocated or no ID is
+    /// assigned.
+    ///
+    /// A pointer allocated earlier has a smaller ID. The cloned instances share the sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default().with_alloc_ids();
+    /// let mut header = TestBox::new(0_u32, alloc.clone());
+    /// let mut body = TestBox::new(0_u64, alloc.clone());
+    ///
+    /// let header_id = alloc.allocation_id_of(&mut *header as *mut u32 as *mut u8).unwrap();
+    /// let body_id = alloc.allocation_id_of(&mut *body as *mut u64 as *mut u8).unwrap();
+    /// assert!(header_id < body_id);
+    /// ```
+    pub fn allocation_id_of(&self, ptr: *mut u8) -> Option<u64> {
+        self.lock_allocatings()
+            .get(&Addr::from(ptr))
+            .and_then(|a| a.id)
+    }
+
+    /// Returns how many times the lock of the tracking information was held by another thread
+    /// when this allocator (or one of the cloned instances) tried to acquire it.
+    ///
+    /// It is always 0 unless `TestAlloc` is shared among threads.
+    /// It helps to detect `TestAlloc` itself is a bottleneck of a multi-threaded test.
+    pub fn lock_contention_count(&self) -> u64 {
+        self.contention_count.load(Ordering::Relaxed)
+    }
+
+    fn lock_allocatings(&self) -> MutexGuard<'_, HashMap<Addr, Allocation, S>> {
+        self.lock(&self.allocatings)
+    }
+
+    // Acquires the lock of `mutex` counting the contention.
+    fn lock<'a, T>(&self, mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        match mutex.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => {
+                self.contention_count.fetch_add(1, Ordering::Relaxed);
+                mutex.lock().unwrap()
+            }
+            Err(TryLockError::Poisoned(_)) => mutex.lock().unwrap(),
+        }
+    }
+
+    /// Returns the violations recorded so far.
+    ///
+    /// It is always empty under `ViolationPolicy::Panic` .
+    pub fn violations(&self) -> Vec<Violation> {
+        self.lock(&self.violations).clone()
+    }
+
+    /// Returns true if any violation has been recorded, or false.
+    pub fn has_violations(&self) -> bool {
+        !self.lock(&self.violations).is_empty()
+    }
+
+    // The prefix of the messages to identify `self` .
+    fn label(&self) -> String {
+        match self.name.as_deref() {
+            None => String::new(),
+            Some(name) => format!("TestAlloc '{}': ", name),
+        }
+    }
+
+    fn violate(&self, violation: Violation) {
+        match self.violation_policy {
+            ViolationPolicy::Panic => panic!("{}{}", self.label(), violation),
+            ViolationPolicy::Record => self.lock(&self.violations).push(violation),
+        }
+    }
+
+    /// Returns the list of pointers and layouts that were allocated and not deallocated.
+    /// The returned value is sorted by the pointer.
+    pub fn providing_pointers(&self) -> Vec<(*mut u8, Layout)> {
+        let mut ret: Vec<(*mut u8, Layout)> = self
+            .lock_allocatings()
+            .iter()
+            .map(|(addr, a)| (addr.ptr(), a.layout))
+            .collect();
+        ret.sort_unstable_by_key(|&(ptr, _)| ptr);
+        ret
+    }
+
+    /// Returns the address and layout of the `i` -th pointer being allocated in the order of the
+    /// address, or `None` if `i` is not less than the number of the pointers.
+    ///
+    /// It sorts all the pointers on every call. It is useful when the order of the addresses is
+    /// known; e.g. a bump allocator always returns an increasing address.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::GAlloc;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let layout = Layout::new::<u64>();
+    /// let ptr = unsafe { alloc.alloc(layout) };
+    ///
+    /// assert_eq!(Some((ptr as usize, layout)), alloc.live_alloc_at_index(0));
+    /// assert_eq!(None, alloc.live_alloc_at_index(1));
+    /// unsafe { alloc.dealloc(ptr, layout) };
+    /// ```
+    pub fn live_alloc_at_index(&self, i: usize) -> Option<(usize, Layout)> {
+        self.providing_pointers()
+            .get(i)
+            .map(|&(ptr, layout)| (self.addr_of(ptr), layout))
+    }
+
+    /// Returns `Ok` if every pointer being allocated is aligned to the alignment of its layout,
+    /// or returns the first violation in the order of the address.
+    ///
+    /// The backend allocator always satisfies it unless it is buggy.
+    pub fn check_alignment_invariant(&self) -> Result<(), AlignmentViolation> {
+        match self
+            .providing_pointers()
+            .into_iter()
+            .find(|&(ptr, layout)| self.addr_of(ptr) % layout.align() != 0)
+        {
+            None => Ok(()),
+            Some((ptr, layout)) => {
+                let ptr = self.addr_of(ptr);
+                Err(AlignmentViolation {
+                    ptr,
+                    layout,
+                    actual_align: 1 << ptr.trailing_zeros(),
+                })
+            }
+        }
+    }
+
+    /// Asserts that no two pointers being allocated overlap in the address space.
+    ///
+    /// The backend allocator never returns overlapping memory unless it is buggy.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the two overlapping allocations if any.
+    pub fn verify_no_overlap(&self) {
+        let pointers = self.providing_pointers();
+        let overlap = pointers.windows(2).find(|w| {
+            let (ptr, layout) = w[0];
+            self.addr_of(ptr) + layout.size() > self.addr_of(w[1].0)
+        });
+
+        if let Some(w) = overlap {
+            panic!(
+                "{}{:p} ({:?}) overlaps {:p} ({:?}).",
+                self.label(),
+                w[0].0,
+                w[0].1,
+                w[1].0,
+                w[1].1
+            );
+        }
+    }
+
+    /// Returns the pointers and layouts that were allocated and not deallocated as a JSON array of
+    /// `{"ptr": "0x...", "size": N, "align": M}` sorted by the address.
+    ///
+    /// It helps CI to parse the leak report. See also [`AllocationSnapshot`] .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::GAlloc;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = GAlloc::default();
+    /// assert_eq!("[]", alloc.export_json());
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// let ptr = unsafe { alloc.alloc(layout) };
+    /// let expected = format!(r#"[{{"ptr":"{:#x}","size":8,"align":8}}]"#, ptr as usize);
+    /// assert_eq!(expected, alloc.export_json());
+    ///
+    /// unsafe { alloc.dealloc(ptr, layout) };
+    /// ```
+    ///
+    /// [`AllocationSnapshot`]: crate::AllocationSnapshot
+    #[cfg(feature = "serde")]
+    pub fn export_json(&self) -> String {
+        let allocations = self
+            .providing_pointers()
+            .into_iter()
+            .map(|(ptr, layout)| AllocationRecord {
+                ptr: self.addr_of(ptr),
+                size: layout.size(),
+                align: layout.align(),
+            })
+            .collect();
+        serde_json::to_string(&AllocationSnapshot { allocations }).unwrap()
+    }
+
+    /// Returns the sorted addresses of the pointers that were allocated and not deallocated.
+    pub fn alloc_sorted_addresses(&self) -> Vec<usize> {
+        let mut ret: Vec<usize> = self
+            .lock_allocatings()
+            .keys()
+            .map(|addr| self.addr_of(addr.ptr()))
+            .collect();
+        ret.sort_unstable();
+        ret
+    }
+
+    /// Allocates memory as `alloc` , and returns `None` if the backend allocator fails.
+    ///
+    /// See also [`try_dealloc`] .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, NeverAlloc, TestAlloc};
+    /// use std::alloc::Layout;
+    ///
+    /// let layout = Layout::new::<u64>();
+    ///
+    /// let alloc = GAlloc::default();
+    /// let ptr = alloc.try_alloc(layout).unwrap();
+    /// unsafe { alloc.try_dealloc(ptr, layout) };
+    ///
+    /// let alloc = TestAlloc::from(NeverAlloc);
+    /// assert_eq!(None, alloc.try_alloc(layout));
+    /// ```
+    ///
+    /// [`try_dealloc`]: Self::try_dealloc
+    pub fn try_alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        NonNull::new(unsafe { self.alloc(layout) })
+    }
+
+    /// Allocates memory via the nightly `Allocator::allocate` of `self` .
+    ///
+    /// The backend allocator implements only `GlobalAlloc` , so the request is tracked as well
+    /// as `alloc` . Unlike `alloc` , no memory is allocated for a zero-sized layout.
+    #[cfg(feature = "allocator_api")]
+    pub fn try_alloc_layout(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Allocator::allocate(self, layout)
+    }
+
+    /// Deallocates `ptr` as `dealloc` .
+    ///
+    /// # Safety
+    ///
+    /// The safety requirements are the same as `GlobalAlloc::dealloc` .
+    pub unsafe fn try_dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.dealloc(ptr.as_ptr(), layout);
+    }
+
+    /// Attaches `annotation` to `ptr` being allocated, replacing the previous one if any.
+    ///
+    /// The annotation is printed with the leak report when `self` is dropped, so that the
+    /// report tells what the leaked memory is for. It follows `ptr` on `realloc` .
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` is not being allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use gharial::GAlloc;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let ptr = unsafe { alloc.alloc(Layout::new::<u64>()) };
+    /// alloc.annotate(ptr, "node for key=42".to_string());
+    ///
+    /// // Panics with "... is deallocated. [\"node for key=42\"]"
+    /// drop(alloc);
+    /// ```
+    pub fn annotate(&self, ptr: *mut u8, annotation: String) {
+        let found = match self.lock_allocatings().get_mut(&Addr::from(ptr)) {
+            Some(a) => {
+                a.annotation = Some(annotation);
+                true
+            }
+            None => false,
+        };
+        assert!(
+            found,
+            "{}TestAlloc::annotate() is passed {:p}, which is not allocated.",
+            self.label(),
+            ptr
+        );
+    }
+
+    /// Allocates memory as `alloc` and attaches `tag` to the allocation.
+    ///
+    /// The tag is forgotten when the pointer is deallocated.
+    /// See also [`allocations_with_tag`] .
+    ///
+    /// # Safety
+    ///
+    /// The safety requirements are the same as `GlobalAlloc::alloc` .
+    ///
+    /// [`allocations_with_tag`]: Self::allocations_with_tag
+    pub unsafe fn alloc_tagged(&self, layout: Layout, tag: u64) -> *mut u8 {
+        let ptr = self.alloc(layout);
+        if !ptr.is_null() {
+            self.lock(&self.tagged_allocations)
+                .insert(Addr::from(ptr), tag);
+        }
+        ptr
+    }
+
+    /// Returns the list of pointers and layouts that were allocated via [`alloc_tagged`] with
+    /// `tag` and not deallocated.
+    /// The returned value is sorted by the pointer.
+    ///
+    /// [`alloc_tagged`]: Self::alloc_tagged
+    pub fn allocations_with_tag(&self, tag: u64) -> Vec<(*mut u8, Layout)> {
+        let allocatings = self.lock_allocatings();
+        let tagged_allocations = self.lock(&self.tagged_allocations);
+
+        let mut ret: Vec<(*mut u8, Layout)> = tagged_allocations
+            .iter()
+            .filter(|(_, &t)| t == tag)
+            // `dealloc` can be running on another thread.
+            .filter_map(|(addr, _)| allocatings.get(addr).map(|a| (addr.ptr(), a.layout)))
+            .collect();
+        ret.sort_unstable_by_key(|&(ptr, _)| ptr);
+        ret
+    }
+
+    /// Returns `Ok` if all the allocated pointers have been deallocated, or returns the pointers
+    /// and layouts that are not deallocated yet sorted by the pointer.
+    ///
+    /// This is the same check as that on the drop, however, it does not panic.
+    pub fn check_leaks(&self) -> Result<(), Vec<(*mut u8, Layout)>> {
+        let leaks = self.providing_pointers();
+        if leaks.is_empty() {
+            Ok(())
+        } else {
+            Err(leaks)
+        }
+    }
+
+    /// Returns the number of pointers that were allocated and not deallocated.
+    pub fn allocation_count(&self) -> usize {
+        self.lock_allocatings().len()
+    }
+
+    /// Calls `f` and returns the result and how much the number of the live allocations
+    /// increased during the call.
+    ///
+    /// The difference is positive if `f` leaks, or 0 if `f` deallocates everything it
+    /// allocates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, GBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let (five, diff) = alloc.measure(|| GBox::new(5, alloc.clone()));
+    /// assert_eq!(1, diff);
+    ///
+    /// let ((), diff) = alloc.measure(|| drop(five));
+    /// assert_eq!(-1, diff);
+    /// ```
+    pub fn measure<F, R>(&self, f: F) -> (R, i64)
+    where
+        F: FnOnce() -> R,
+    {
+        let before = self.allocation_count() as i64;
+        let ret = f();
+        let after = self.allocation_count() as i64;
+        (ret, after - before)
+    }
+
+    /// Returns the layouts of the pointers that were allocated and not deallocated grouped by the
+    /// thread which allocated them.
+    ///
+    /// It helps to find memory that one thread allocates and another thread is expected to free.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::{GlobalAlloc, Layout};
+    /// use std::thread;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let layout = Layout::new::<u64>();
+    /// let _a = TestBox::new(0_u8, alloc.clone());
+    ///
+    /// let (ptr, id) = thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let ptr = unsafe { alloc.alloc(layout) };
+    ///         (ptr as usize, thread::current().id())
+    ///     })
+    ///     .join()
+    ///     .unwrap()
+    /// });
+    ///
+    /// let by_thread = alloc.allocations_by_thread();
+    /// assert_eq!(vec![Layout::new::<u8>()], by_thread[&thread::current().id()]);
+    /// assert_eq!(vec![layout], by_thread[&id]);
+    ///
+    /// unsafe { alloc.dealloc(ptr as *mut u8, layout) };
+    /// ```
+    pub fn allocations_by_thread(&self) -> HashMap<ThreadId, Vec<Layout>> {
+        let mut ret: HashMap<ThreadId, Vec<Layout>> = HashMap::new();
+        for a in self.lock_allocatings().values() {
+            ret.entry(a.thread).or_default().push(a.layout);
+        }
+        ret
+    }
+
+    /// Returns the number of pointers that were allocated with `layout` and not deallocated.
+    pub fn alloc_count_for_layout(&self, layout: Layout) -> usize {
+        self.lock_allocatings()
+            .values()
+            .filter(|a| a.layout == layout)
+            .count()
+    }
+
+    /// Deallocates all the pointers that were allocated with `layout` and not deallocated, and
+    /// returns the number of them.
+    ///
+    /// It is useful for the teardown of a test when the destructor of the container is broken;
+    /// the leak check still works for the other layouts.
+    ///
+    /// # Safety
+    ///
+    /// The purged pointers must not be used nor deallocated any more.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::GAlloc;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let node = Layout::new::<[usize; 4]>();
+    ///
+    /// unsafe {
+    ///     alloc.alloc(node);
+    ///     alloc.alloc(node);
+    ///     let ptr = alloc.alloc(Layout::new::<u8>());
+    ///
+    ///     assert_eq!(2, alloc.purge_layout(node));
+    ///     assert_eq!(1, alloc.allocation_count());
+    ///     alloc.dealloc(ptr, Layout::new::<u8>());
+    /// }
+    /// ```
+    pub unsafe fn purge_layout(&self, layout: Layout) -> usize {
+        let ptrs: Vec<*mut u8> = self
+            .lock_allocatings()
+            .iter()
+            .filter(|(_, a)| a.layout == layout)
+            .map(|(addr, _)| addr.ptr())
+            .collect();
+
+        for &ptr in ptrs.iter() {
+            self.dealloc(ptr, layout);
+        }
+        ptrs.len()
+    }
+
+    /// Returns the distinct layouts of the pointers that were allocated and not deallocated.
+    ///
+    /// The returned value is sorted by the size and then by the alignment. (`Layout` does not
+    /// implement `Ord` .)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::Layout;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let _a = TestBox::new(1_u64, alloc.clone());
+    /// let _b = TestBox::new(2_u8, alloc.clone());
+    /// let _c = TestBox::new(3_u64, alloc.clone());
+    ///
+    /// assert_eq!(
+    ///     vec![Layout::new::<u8>(), Layout::new::<u64>()],
+    ///     alloc.live_layouts()
+    /// );
+    /// ```
+    pub fn live_layouts(&self) -> Vec<Layout> {
+        let mut ret: Vec<Layout> = self.lock_allocatings().values().map(|a| a.layout).collect();
+        ret.sort_unstable_by_key(|l| (l.size(), l.align()));
+        ret.dedup();
+        ret
+    }
+
+    /// Returns the total bytes of the pointers that were allocated and not deallocated.
+    pub fn allocation_bytes(&self) -> usize {
+        self.lock_allocatings()
+            .values()
+            .map(|a| a.layout.size())
+            .sum()
+    }
+
+    /// Returns the snapshot of the statistics.
+    pub fn stats(&self) -> AllocStats {
+        *self.lock(&self.stats)
+    }
+
+    /// Clears the historical statistics.
+    ///
+    /// `total_alloc_count` and `total_dealloc_count` of [`AllocStats`] are set to 0, and the
+    /// peaks are set to the current live values. The size histogram is cleared as well.
+    ///
+    /// The live allocations are still tracked; this method does not affect the leak check.
+    /// It is useful to exclude the warm-up phase of a test from the statistics.
+    pub fn reset_stats(&self) {
+        self.lock(&self.stats).reset();
+        self.lock(&self.size_histogram).clear();
+    }
+
+    /// Returns a map from the allocation size in bytes to how many times memory of the size has
+    /// been allocated.
+    ///
+    /// The deallocations do not affect the result.
+    pub fn size_histogram(&self) -> BTreeMap<usize, u64> {
+        self.lock(&self.size_histogram).clone()
+    }
+
+    /// Asserts that the live bytes have increased by exactly `by_bytes` since `before` .
+    ///
+    /// # Panics
+    ///
+    /// Panics with both `before` and the current statistics if the assertion fails.
+    pub fn assert_grew(&self, before: &AllocStats, by_bytes: usize) {
+        let after = self.stats();
+        if before.live_bytes.checked_add(by_bytes) != Some(after.live_bytes) {
+            panic!(
+                "{}The live bytes are expected to grow by {}.\nbefore: {:?}\nafter: {:?}",
+                self.label(),
+                by_bytes,
+                before,
+                after
+            );
+        }
+    }
+
+    /// Asserts that the live bytes have decreased by exactly `by_bytes` since `before` .
+    ///
+    /// # Panics
+    ///
+    /// Panics with both `before` and the current statistics if the assertion fails.
+    pub fn assert_shrank(&self, before: &AllocStats, by_bytes: usize) {
+        let after = self.stats();
+        if before.live_bytes.checked_sub(by_bytes) != Some(after.live_bytes) {
+            panic!(
+                "{}The live bytes are expected to shrink by {}.\nbefore: {:?}\nafter: {:?}",
+                self.label(),
+                by_bytes,
+                before,
+                after
+            );
+        }
+    }
+
+    /// Returns the layout `ptr` is allocated with, or `None` if `ptr` is not being allocated.
+    pub(crate) fn layout_of(&self, ptr: *mut u8) -> Option<Layout> {
+        self.lock_allocatings()
+            .get(&Addr::from(ptr))
+            .map(|a| a.layout)
+    }
+
+    /// Allocates memory for `T` , writes `val` , reads it back, and frees the memory.
+    ///
+    /// Returns true if the read value equals to `val` , or false. (Returns false if the backend
+    /// allocator fails to allocate.) It is a sanity check that the backend allocator returns
+    /// writable memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, NeverAlloc, TestAlloc};
+    ///
+    /// assert!(GAlloc::default().verify_write_read(0x1234_5678_u64));
+    /// assert!(!TestAlloc::<NeverAlloc>::default().verify_write_read(0_u8));
+    /// ```
+    pub fn verify_write_read<T>(&self, val: T) -> bool
+    where
+        T: Copy + PartialEq,
+    {
+        let layout = Layout::new::<T>();
+        if layout.size() == 0 {
+            return true;
+        }
+
+        unsafe {
+            let ptr = self.alloc(layout) as *mut T;
+            if ptr.is_null() {
+                return false;
+            }
+
+            ptr.write_volatile(val);
+            let ret = ptr.read_volatile() == val;
+            self.dealloc(ptr as *mut u8, layout);
+            ret
+        }
+    }
+
+    /// Returns the number of the live allocations that were allocated via
+    /// `GlobalAlloc::alloc_zeroed` .
+    pub fn zeroed_allocation_count(&self) -> usize {
+        self.lock_allocatings()
+            .values()
+            .filter(|a| a.zeroed)
+            .count()
+    }
+
+    /// Returns the time elapsed since the last successful allocation, or `None` if nothing has
+    /// been allocated yet.
+    pub fn time_since_last_alloc(&self) -> Option<Duration> {
+        match self.last_alloc_time.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(Duration::from_nanos(
+                (self.nanos_since_creation() + 1).saturating_sub(last),
+            )),
+        }
+    }
+
+    // `Instant` is monotonic unlike `SystemTime` ; a step of the wall clock does not matter.
+    fn nanos_since_creation(&self) -> u64 {
+        self.created_at.elapsed().as_nanos() as u64
+    }
+
+    /// Returns how long the oldest pointer that is not deallocated yet has been allocated, or
+    /// `None` if no pointer is being allocated.
+    pub fn oldest_allocation_age(&self) -> Option<Duration> {
+        self.lock_allocatings()
+            .values()
+            .map(|a| a.allocated_at)
+            .min()
+            .map(|t| t.elapsed())
+    }
+}
+
+// `Allocator` accepts a zero-sized layout while `GlobalAlloc` does not define the behavior.
+// `TestAlloc` does not delegate such a request to the backend allocator, but returns a dangling
+// pointer instead like `std::alloc::Global` .
+#[cfg(feature = "allocator_api")]
+unsafe impl<A, S> Allocator for TestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = if layout.size() == 0 {
+            core::ptr::without_provenance_mut(layout.align())
+        } else {
+            unsafe { GlobalAlloc::alloc(self, layout) }
+        };
+
+        match NonNull::new(ptr) {
+            None => Err(AllocError),
+            Some(ptr) => Ok(NonNull::slice_from_raw_parts(ptr, layout.size())),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            GlobalAlloc::dealloc(self, ptr.as_ptr(), layout);
+        }
+    }
+}
+
+// Two instances are equal if and only if they share the tracking information.
+impl<A, S> PartialEq<Self> for TestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    fn eq(&self, rh: &Self) -> bool {
+        Self::same_allocator(self, rh)
+    }
+}
+
+impl<A, S> Eq for TestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+}
+
+// `GlobalAlloc` can not be implemented for `Arc<TestAlloc<A>>` because of the orphan rule.
+// Implement it for the reference instead, so that `&*arc` can be used as an allocator.
+unsafe impl<A, S> GlobalAlloc for &TestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        (**self).alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        (**self).dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        (**self).realloc(ptr, layout, new_size)
+    }
+}
+
+impl<A, S> fmt::Debug for TestAlloc<A, S>
+where
+    A: GlobalAlloc + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestAlloc")
+            .field("alloc", &self.alloc)
+            .field("info", &Arc::as_ptr(&self.allocatings))
+            .finish()
+    }
+}
+
+// `Send` is not implemented automatically because the key type of the `allocating` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Send` manually.
+unsafe impl<A, S> Send for TestAlloc<A, S>
+where
+    A: GlobalAlloc + Send,
+    S: BuildHasher + Send,
+{
+}
+
+// `Send` is not implemented automatically because the key type of the `allocating` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Send` manually.
+unsafe impl<A, S> Sync for TestAlloc<A, S>
+where
+    A: GlobalAlloc + Send + Sync,
+    S: BuildHasher + Send,
+{
+}
+
+/// `NeverAlloc` is an implementation for `GlobalAlloc` , which always fails.
+/// For example, `NeverAlloc::alloc` always returns a null pointer.
+#[derive(Clone, Copy, Debug)]
+pub struct NeverAlloc;
+
+impl Default for NeverAlloc {
+    fn default() -> Self {
+        Self
+    }
+}
+
+unsafe impl GlobalAlloc for NeverAlloc {
+    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+        core::ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        panic!("Method NeverAlloc.dealloc() is called.");
+    }
+}
+
+/// `MaybeAlloc` is an implementation for `GlobalAlloc` , which occasionally fails to allocate.
+///
+/// It is a wrapper of another `GlobalAlloc` , and delegates the requests to the inner, however, sometimes fails to allocate
+/// memory on purpose. i.e. `MaybeAlloc::alloc` can return null pointer before memory exhaustion.
+///
+/// The failure properbility is 1/16. (Instances created by [`with_exact_failures`] fail the
+/// first N requests instead.)
+///
+/// The default backend is [`GAlloc`] . Because `MaybeAlloc` never delegates the failing requests,
+/// the backend `TestAlloc` tracks only the pointers that `MaybeAlloc::alloc` returns
+/// successfully. See also [`inner_test_alloc`] .
+///
+/// [`inner_test_alloc`]: Self::inner_test_alloc
+/// [`with_exact_failures`]: Self::with_exact_failures
+pub struct MaybeAlloc<A = TestAlloc<System>>
+where
+    A: GlobalAlloc,
+{
+    alloc: A,
+    // `None` means failing at random.
+    remaining_failures: Option<Arc<AtomicUsize>>,
+}
+
+// `loom::sync::Arc` does not implement `Debug` .
+impl<A> fmt::Debug for MaybeAlloc<A>
+where
+    A: GlobalAlloc + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaybeAlloc")
+            .field("alloc", &self.alloc)
+            .field(
+                "remaining_failures",
+                &self
+                    .remaining_failures
+                    .as_ref()
+                    .map(|n| n.load(Ordering::Relaxed)),
+            )
+            .finish()
+    }
+}
+
+impl<A> Default for MaybeAlloc<A>
+where
+    A: GlobalAlloc + Default,
+{
+    fn default() -> Self {
+        Self::from(A::default())
+    }
+}
+
+impl<A> From<A> for MaybeAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    fn from(alloc: A) -> Self {
+        Self {
+            alloc,
+            remaining_failures: None,
+        }
+    }
+}
+
+impl<A> Clone for MaybeAlloc<A>
+where
+    A: GlobalAlloc + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            alloc: self.alloc.clone(),
+            remaining_failures: self.remaining_failures.clone(),
+        }
+    }
+}
+
+impl<A> MaybeAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    /// Creates a new instance which fails the first `n` allocation requests and always
+    /// delegates the subsequent requests to `inner` .
+    ///
+    /// The cloned instances share the counter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, MaybeAlloc};
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = MaybeAlloc::with_exact_failures(GAlloc::default(), 2);
+    /// let layout = Layout::new::<u64>();
+    ///
+    /// unsafe {
+    ///     assert!(alloc.alloc(layout).is_null());
+    ///     assert_eq!(1, alloc.failures_remaining());
+    ///     assert!(alloc.alloc(layout).is_null());
+    ///     assert_eq!(0, alloc.failures_remaining());
+    ///
+    ///     let ptr = alloc.alloc(layout);
+    ///     assert!(!ptr.is_null());
+    ///     alloc.dealloc(ptr, layout);
+    /// }
+    /// ```
+    pub fn with_exact_failures(inner: A, n: usize) -> Self {
+        Self {
+            alloc: inner,
+            remaining_failures: Some(Arc::new(AtomicUsize::new(n))),
+        }
+    }
+
+    /// Returns how many allocation requests will fail before succeeding.
+    ///
+    /// It is always 0 unless `self` is created by [`with_exact_failures`] .
+    ///
+    /// [`with_exact_failures`]: Self::with_exact_failures
+    pub fn failures_remaining(&self) -> usize {
+        self.remaining_failures
+            .as_ref()
+            .map_or(0, |n| n.load(Ordering::Relaxed))
+    }
+
+    fn should_fail(&self) -> bool {
+        match self.remaining_failures.as_ref() {
+            None => rand::random::<u8>() % 16 == 0,
+            Some(n) => n
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok(),
+        }
+    }
+}
+
+impl<A, S> MaybeAlloc<TestAlloc<A, S>>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    /// Provides a reference to the backend `TestAlloc` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, MaybeAlloc};
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = MaybeAlloc::<GAlloc>::default();
+    /// let layout = Layout::new::<u64>();
+    ///
+    /// let ptr = unsafe { alloc.alloc(layout) };
+    /// if ptr.is_null() {
+    ///     assert!(alloc.inner_test_alloc().check_leaks().is_ok());
+    /// } else {
+    ///     assert!(alloc.inner_test_alloc().check_leaks().is_err());
+    ///     unsafe { alloc.dealloc(ptr, layout) };
+    /// }
+    /// ```
+    pub fn inner_test_alloc(&self) -> &TestAlloc<A, S> {
+        &self.alloc
+    }
+}
+
+unsafe impl<A> GlobalAlloc for MaybeAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.should_fail() {
+            core::ptr::null_mut()
+        } else {
+            self.alloc.alloc(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr.is_null() {
+            panic!("Null pointer is passed to method GlobalAlloc.dealloc().");
+        }
+        self.alloc.dealloc(ptr, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_capacity() {
+        let alloc = GAlloc::with_capacity(System, 16);
+        assert!(16 <= alloc.allocatings.lock().unwrap().capacity());
+
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            assert_eq!(vec![(ptr, layout)], alloc.providing_pointers());
+            alloc.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn providing_pointers_is_sorted() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+
+        let ptrs: Vec<*mut u8> = (0..8).map(|_| unsafe { alloc.alloc(layout) }).collect();
+
+        let mut expected = ptrs.clone();
+        expected.sort();
+        let actual: Vec<*mut u8> = alloc.providing_pointers().iter().map(|&(p, _)| p).collect();
+        assert_eq!(expected, actual);
+
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+    }
+
+    #[test]
+    fn allocation_count_and_bytes() {
+        let alloc = GAlloc::default();
+        assert_alloc_count!(alloc, 0);
+        assert_alloc_bytes!(alloc, 0);
+
+        let layout0 = Layout::new::<u64>();
+        let layout1 = Layout::new::<[u16; 3]>();
+        unsafe {
+            let ptr0 = alloc.alloc(layout0);
+            let ptr1 = alloc.alloc(layout1);
+            assert_alloc_count!(alloc, 2);
+            assert_alloc_bytes!(alloc, 14);
+
+            alloc.dealloc(ptr0, layout0);
+            assert_alloc_count!(alloc, 1);
+            assert_alloc_bytes!(alloc, 6);
+
+            alloc.dealloc(ptr1, layout1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 1 live allocations, got 0")]
+    fn assert_alloc_count_fails() {
+        let alloc = GAlloc::default();
+        assert_alloc_count!(alloc, 1);
+    }
+
+    #[test]
+    fn alloc_tagged() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr0 = alloc.alloc_tagged(layout, 1);
+            let ptr1 = alloc.alloc_tagged(layout, 2);
+            let ptr2 = alloc.alloc_tagged(layout, 1);
+            let ptr3 = alloc.alloc(layout);
+
+            let mut expected = vec![(ptr0, layout), (ptr2, layout)];
+            expected.sort_unstable_by_key(|&(ptr, _)| ptr);
+            assert_eq!(expected, alloc.allocations_with_tag(1));
+            assert_eq!(vec![(ptr1, layout)], alloc.allocations_with_tag(2));
+            assert_eq!(4, alloc.allocation_count());
+
+            alloc.dealloc(ptr0, layout);
+            assert_eq!(vec![(ptr2, layout)], alloc.allocations_with_tag(1));
+
+            alloc.dealloc(ptr1, layout);
+            alloc.dealloc(ptr2, layout);
+            alloc.dealloc(ptr3, layout);
+            assert!(alloc.allocations_with_tag(1).is_empty());
+        }
+    }
+
+    #[test]
+    fn with_oom_handler() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let alloc = {
+            let count = count.clone();
+            TestAlloc::from(NeverAlloc).with_oom_handler(move |layout| {
+                assert_eq!(Layout::new::<u64>(), layout);
+                count.fetch_add(1, Ordering::Relaxed);
+            })
+        };
+
+        let ptr = unsafe { alloc.alloc(Layout::new::<u64>()) };
+        assert!(ptr.is_null());
+        assert_eq!(1, count.load(Ordering::Relaxed));
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn alloc_hook() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let alloc = {
+            let count = count.clone();
+            TestAlloc::from(MaybeAlloc::<System>::default()).with_alloc_hook(move |layout, ptr| {
+                assert_eq!(Layout::new::<u64>(), layout);
+                assert!(!ptr.is_null());
+                count.fetch_add(1, Ordering::Relaxed);
+            })
+        };
+
+        let layout = Layout::new::<u64>();
+        let mut ptrs = Vec::new();
+        for _ in 0..64 {
+            let ptr = unsafe { alloc.alloc(layout) };
+            if !ptr.is_null() {
+                ptrs.push(ptr);
+            }
+        }
+        assert_eq!(ptrs.len(), count.load(Ordering::Relaxed));
+
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+    }
+
+    #[test]
+    fn dealloc_hook() {
+        let freed = Arc::new(Mutex::new(Vec::new()));
+        let alloc = {
+            let freed = freed.clone();
+            GAlloc::default().with_dealloc_hook(move |ptr, layout| {
+                freed.lock().unwrap().push((ptr as usize, layout));
+            })
+        };
+
+        let layout = Layout::new::<u64>();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(freed.lock().unwrap().is_empty());
+
+        unsafe { alloc.dealloc(ptr, layout) };
+        assert_eq!(vec![(ptr as usize, layout)], *freed.lock().unwrap());
+    }
+
+    #[test]
+    fn allocation_id_of() {
+        let layout = Layout::new::<u64>();
+
+        let alloc = GAlloc::default();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(None, alloc.allocation_id_of(ptr));
+        unsafe { alloc.dealloc(ptr, layout) };
+
+        let alloc = GAlloc::default().with_alloc_ids();
+        let cloned = alloc.clone();
+        let ptrs: Vec<_> = (0..4)
+            .map(|i| unsafe {
+                if i % 2 == 0 {
+                    alloc.alloc(layout)
+                } else {
+                    cloned.alloc(layout)
+                }
+            })
+            .collect();
+        let ids: Vec<_> = ptrs
+            .iter()
+            .map(|&ptr| alloc.allocation_id_of(ptr).unwrap())
+            .collect();
+        assert_eq!(vec![0, 1, 2, 3], ids);
+
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) };
+            assert_eq!(None, alloc.allocation_id_of(ptr));
+        }
+    }
+
+    #[test]
+    fn realloc() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<[u32; 2]>();
+
+        unsafe {
+            let ptr = alloc.alloc_tagged(layout, 1) as *mut [u32; 2];
+            ptr.write([1, 2]);
+
+            let ptr = alloc.realloc(ptr as *mut u8, layout, 16) as *mut u32;
+            assert!(!ptr.is_null());
+            assert_eq!([1, 2], *(ptr as *mut [u32; 2]));
+
+            let new_layout = Layout::from_size_align(16, 4).unwrap();
+            assert_eq!(
+                vec![(ptr as *mut u8, new_layout)],
+                alloc.providing_pointers()
+            );
+            assert_eq!(
+                vec![(ptr as *mut u8, new_layout)],
+                alloc.allocations_with_tag(1)
+            );
+
+            let stats = alloc.stats();
+            assert_eq!(1, stats.live_count);
+            assert_eq!(16, stats.live_bytes);
+            assert_eq!(2, stats.total_alloc_count);
+            assert_eq!(1, stats.total_dealloc_count);
+
+            alloc.dealloc(ptr as *mut u8, new_layout);
+        }
+    }
+
+    #[test]
+    fn realloc_failure_keeps_pointer() {
+        #[derive(Default)]
+        struct NoRealloc;
+
+        unsafe impl GlobalAlloc for NoRealloc {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                System.alloc(layout)
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                System.dealloc(ptr, layout)
+            }
+
+            unsafe fn realloc(&self, _: *mut u8, _: Layout, _: usize) -> *mut u8 {
+                core::ptr::null_mut()
+            }
+        }
+
+        let alloc = TestAlloc::from(NoRealloc);
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            assert!(alloc.realloc(ptr, layout, 32).is_null());
+            assert_eq!(vec![(ptr, layout)], alloc.providing_pointers());
+            assert_eq!(1, alloc.stats().total_alloc_count);
+            alloc.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn check_alignment_invariant() {
+        let alloc = GAlloc::default().with_drop_policy(DropPolicy::IgnoreOnLeak);
+        let layout = Layout::from_size_align(8, 64).unwrap();
+
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(Ok(()), alloc.check_alignment_invariant());
+
+        // Pretends that the backend allocator returned a misaligned pointer.
+        let misaligned = ptr.wrapping_add(4);
+        alloc
+            .lock_allocatings()
+            .insert(Addr::from(misaligned), Allocation::from(layout));
+        let err = alloc.check_alignment_invariant().unwrap_err();
+        assert_eq!(misaligned as usize, err.ptr);
+        assert_eq!(layout, err.layout);
+        assert_eq!(4, err.actual_align);
+
+        alloc.lock_allocatings().remove(&Addr::from(misaligned));
+        unsafe { alloc.dealloc(ptr, layout) };
+    }
+
+    #[test]
+    #[should_panic(expected = "invariant violated")]
+    fn assert_stable() {
+        let alloc = GAlloc::default().with_drop_policy(DropPolicy::IgnoreOnLeak);
+        alloc.assert_stable();
+
+        alloc.lock_allocatings().insert(
+            Addr::from(core::ptr::null_mut()),
+            Allocation::from(Layout::new::<u8>()),
+        );
+        alloc.assert_stable();
+    }
+
+    #[test]
+    fn with_strict_provenance() {
+        let alloc = GAlloc::default().with_strict_provenance();
+        let cloned = alloc.clone_unshared();
+        let layout = Layout::new::<u64>();
+
+        for alloc in [alloc, cloned] {
+            unsafe {
+                let ptr = alloc.alloc(layout);
+                assert_eq!(vec![ptr.addr()], alloc.alloc_sorted_addresses());
+                assert_eq!(Ok(()), alloc.check_alignment_invariant());
+
+                alloc.dealloc(ptr, layout);
+                assert_eq!(ptr.addr(), alloc.history().iter().last().unwrap().ptr);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The recent allocations are expected")]
+    fn assert_allocation_order_fails() {
+        let alloc = GAlloc::default();
+        let _a = crate::GBox::new(0_u64, alloc.clone());
+        let _b = crate::GBox::new(0_u8, alloc.clone());
+
+        alloc.assert_allocation_order(&[Layout::new::<u8>()]);
+        alloc.assert_allocation_order(&[Layout::new::<u8>(), Layout::new::<u64>()]);
+    }
+
+    #[test]
+    fn with_max_alloc_size() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let alloc = {
+            let count = count.clone();
+            GAlloc::default()
+                .with_max_alloc_size(16)
+                .with_oom_handler(move |layout| {
+                    assert_eq!(32, layout.size());
+                    count.fetch_add(1, Ordering::Relaxed);
+                })
+        };
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            assert!(!ptr.is_null());
+            assert!(alloc.realloc(ptr, layout, 32).is_null());
+            assert!(alloc
+                .alloc_zeroed(Layout::from_size_align(32, 8).unwrap())
+                .is_null());
+            assert_eq!(vec![(ptr, layout)], alloc.providing_pointers());
+            alloc.dealloc(ptr, layout);
+        }
+        assert_eq!(2, count.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn verify_no_overlap() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Returns the memory shifted by 8 bytes from the previous one.
+        struct Overlapping(std::cell::UnsafeCell<[u64; 4]>, AtomicUsize);
+        unsafe impl Sync for Overlapping {}
+        unsafe impl GlobalAlloc for Overlapping {
+            unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+                let offset = self.1.fetch_add(8, Ordering::Relaxed);
+                (self.0.get() as *mut u8).add(offset)
+            }
+            unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+        }
+
+        let backend = Overlapping(std::cell::UnsafeCell::new([0; 4]), AtomicUsize::new(0));
+        let alloc = TestAlloc::from(backend);
+        let layout = Layout::new::<[u64; 2]>();
+
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            alloc.verify_no_overlap();
+
+            let other = alloc.alloc(layout);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                alloc.verify_no_overlap()
+            }));
+            assert!(result.is_err());
+
+            alloc.dealloc(other, layout);
+            alloc.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn annotate() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            alloc.annotate(ptr, "node".to_string());
+
+            let ptr = alloc.realloc(ptr, layout, 16);
+            let annotation = alloc.lock_allocatings()[&Addr::from(ptr)]
+                .annotation
+                .clone();
+            assert_eq!(Some("node".to_string()), annotation);
+
+            let layout = Layout::from_size_align(16, layout.align()).unwrap();
+            alloc.dealloc(ptr, layout);
+
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                alloc.annotate(ptr, "freed".to_string())
+            }));
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn live_alloc_at_index() {
+        let alloc = GAlloc::default();
+        let small = Layout::new::<u8>();
+        let large = Layout::new::<[u64; 4]>();
+
+        unsafe {
+            let a = alloc.alloc(small);
+            let b = alloc.alloc(large);
+            let (first, second) = if a < b {
+                ((a, small), (b, large))
+            } else {
+                ((b, large), (a, small))
+            };
+
+            assert_eq!(
+                Some((first.0 as usize, first.1)),
+                alloc.live_alloc_at_index(0)
+            );
+            assert_eq!(
+                Some((second.0 as usize, second.1)),
+                alloc.live_alloc_at_index(1)
+            );
+            assert_eq!(None, alloc.live_alloc_at_index(2));
+
+            alloc.dealloc(a, small);
+            alloc.dealloc(b, large);
+        }
+        assert_eq!(None, alloc.live_alloc_at_index(0));
+    }
+
+    #[test]
+    fn oldest_allocation_age() {
+        let alloc = GAlloc::default();
+        assert_eq!(None, alloc.oldest_allocation_age());
+
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr0 = alloc.alloc(layout);
+            std::thread::sleep(Duration::from_millis(20));
+            let ptr1 = alloc.alloc(layout);
+
+            let age0 = alloc.oldest_allocation_age().unwrap();
+            assert!(Duration::from_millis(20) <= age0);
+
+            alloc.dealloc(ptr0, layout);
+            assert!(alloc.oldest_allocation_age().unwrap() < age0);
+
+            alloc.dealloc(ptr1, layout);
+            assert_eq!(None, alloc.oldest_allocation_age());
+        }
+    }
+
+    #[test]
+    fn history() {
+        let alloc = GAlloc::default().with_history_capacity(2);
+        assert!(alloc.history().is_empty());
+
+        let layout0 = Layout::new::<u64>();
+        let layout1 = Layout::new::<u32>();
+        unsafe {
+            let ptr0 = alloc.alloc(layout0);
+            let ptr1 = alloc.alloc(layout1);
+            alloc.dealloc(ptr0, layout0);
+
+            let events: Vec<(AllocEventKind, usize, Layout)> = alloc
+                .history()
+                .iter()
+                .map(|e| (e.kind, e.ptr, e.layout))
+                .collect();
+            let expected = vec![
+                (AllocEventKind::Alloc, ptr1 as usize, layout1),
+                (AllocEventKind::Dealloc, ptr0 as usize, layout0),
+            ];
+            assert_eq!(expected, events);
+            assert_eq!(2, alloc.history().to_string().lines().count());
+
+            alloc.dealloc(ptr1, layout1);
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let alloc = GAlloc::default();
+        assert_eq!(AllocStats::default(), alloc.stats());
+
+        let layout0 = Layout::new::<u64>();
+        let layout1 = Layout::new::<[u8; 24]>();
+        unsafe {
+            let ptr0 = alloc.alloc(layout0);
+            let before = alloc.stats();
+
+            let ptr1 = alloc.alloc(layout1);
+            alloc.assert_grew(&before, 24);
+
+            let before = alloc.stats();
+            alloc.dealloc(ptr0, layout0);
+            alloc.assert_shrank(&before, 8);
+
+            let expected = AllocStats {
+                live_count: 1,
+                live_bytes: 24,
+                total_alloc_count: 2,
+                total_dealloc_count: 1,
+                peak_live_count: 2,
+                peak_live_bytes: 32,
+            };
+            assert_eq!(expected, alloc.stats());
+
+            alloc.dealloc(ptr1, layout1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "expected to grow by 16")]
+    fn assert_grew_fails() {
+        let alloc = GAlloc::default();
+        let before = alloc.stats();
+
+        let layout = Layout::new::<u64>();
+        let _tb = crate::GBox::new(0_u64, alloc.clone());
+        assert_eq!(layout.size(), alloc.stats().live_bytes);
+        alloc.assert_grew(&before, 16);
+    }
+
+    #[test]
+    fn maybe_alloc_tracks_successful_allocations() {
+        let alloc = MaybeAlloc::<GAlloc>::default();
+        let layout = Layout::new::<u64>();
+
+        let ptrs: Vec<*mut u8> = (0..256).map(|_| unsafe { alloc.alloc(layout) }).collect();
+        let mut succeeded: Vec<(*mut u8, Layout)> = ptrs
+            .iter()
+            .filter(|ptr| !ptr.is_null())
+            .map(|&ptr| (ptr, layout))
+            .collect();
+        succeeded.sort_unstable_by_key(|&(ptr, _)| ptr);
+        assert_eq!(Err(succeeded), alloc.inner_test_alloc().check_leaks());
+
+        for ptr in ptrs.into_iter().filter(|ptr| !ptr.is_null()) {
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+        assert_eq!(Ok(()), alloc.inner_test_alloc().check_leaks());
+    }
+
+    #[test]
+    #[cfg(feature = "backtraces")]
+    fn with_backtraces() {
+        let alloc = GAlloc::default().with_backtraces();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            assert!(alloc.allocatings.lock().unwrap()[&Addr::from(ptr)]
+                .backtrace
+                .is_some());
+            alloc.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn lock_contention_count() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            alloc.dealloc(ptr, layout);
+        }
+        assert_eq!(0, alloc.lock_contention_count());
+
+        let guard = alloc.allocatings.lock().unwrap();
+        let handle = {
+            let alloc = alloc.clone();
+            std::thread::spawn(move || unsafe {
+                let ptr = alloc.alloc(layout);
+                alloc.dealloc(ptr, layout);
+            })
+        };
+        while alloc.lock_contention_count() == 0 {
+            std::thread::yield_now();
+        }
+        drop(guard);
+        handle.join().unwrap();
+
+        assert!(1 <= alloc.lock_contention_count());
+    }
+
+    #[test]
+    fn arc() {
+        let alloc = GAlloc::default().into_arc();
+        let cloned = alloc.clone();
+        let alloc = GAlloc::try_from_arc(alloc).unwrap_err();
+
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let r: &GAlloc = &alloc;
+            let ptr = GlobalAlloc::alloc(&r, layout);
+            assert_eq!(1, cloned.allocation_count());
+
+            let r: &GAlloc = &cloned;
+            GlobalAlloc::dealloc(&r, ptr, layout);
+        }
+
+        drop(cloned);
+        let alloc = GAlloc::try_from_arc(alloc).unwrap();
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn size_histogram() {
+        let alloc = GAlloc::default();
+        assert!(alloc.size_histogram().is_empty());
+
+        let layout0 = Layout::new::<u64>();
+        let layout1 = Layout::new::<[u8; 32]>();
+        unsafe {
+            for _ in 0..3 {
+                let ptr = alloc.alloc(layout0);
+                alloc.dealloc(ptr, layout0);
+            }
+            let ptr = alloc.alloc(layout1);
+
+            let expected: BTreeMap<usize, u64> = [(8, 3), (32, 1)].iter().copied().collect();
+            assert_eq!(expected, alloc.size_histogram());
+
+            alloc.dealloc(ptr, layout1);
+        }
+    }
+
+    #[test]
+    fn reset_stats() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr0 = alloc.alloc(layout);
+            let ptr1 = alloc.alloc(layout);
+            alloc.dealloc(ptr1, layout);
+
+            alloc.reset_stats();
+            let expected = AllocStats {
+                live_count: 1,
+                live_bytes: 8,
+                total_alloc_count: 0,
+                total_dealloc_count: 0,
+                peak_live_count: 1,
+                peak_live_bytes: 8,
+            };
+            assert_eq!(expected, alloc.stats());
+            assert!(alloc.size_histogram().is_empty());
+            assert_eq!(vec![(ptr0, layout)], alloc.providing_pointers());
+
+            alloc.dealloc(ptr0, layout);
+            assert_eq!(1, alloc.stats().total_dealloc_count);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The live allocations do not match.")]
+    fn assert_exact_allocations_fails() {
+        let alloc = GAlloc::default();
+        let _tb = crate::GBox::new(0_u32, alloc.clone());
+        assert_exact_allocations!(alloc, [(4, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn same_allocator() {
+        let alloc = GAlloc::default();
+        let cloned = alloc.clone();
+        let other = GAlloc::default();
+
+        assert!(GAlloc::same_allocator(&alloc, &cloned));
+        assert!(!GAlloc::same_allocator(&alloc, &other));
+        assert!(alloc == cloned);
+        assert!(alloc != other);
+
+        fn assert_eq_impl<T: Eq>(_: &T) {}
+        assert_eq_impl(&alloc);
+    }
+
+    #[test]
+    fn time_since_last_alloc() {
+        let alloc = GAlloc::default();
+        assert_eq!(None, alloc.time_since_last_alloc());
+
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            std::thread::sleep(Duration::from_millis(20));
+            alloc.dealloc(ptr, layout);
+        }
+        assert!(Duration::from_millis(20) <= alloc.time_since_last_alloc().unwrap());
+    }
+
+    #[test]
+    fn zeroed_allocation_count() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<[u64; 4]>();
+        unsafe {
+            let p0 = alloc.alloc(layout);
+            let p1 = alloc.alloc_zeroed(layout);
+            assert_eq!(1, alloc.zeroed_allocation_count());
+            assert_eq!(2, alloc.allocation_count());
+            assert!(core::slice::from_raw_parts(p1, layout.size())
+                .iter()
+                .all(|&b| b == 0));
+
+            alloc.dealloc(p1, layout);
+            assert_eq!(0, alloc.zeroed_allocation_count());
+            alloc.dealloc(p0, layout);
+        }
+    }
+
+    #[test]
+    fn drop_while_panicking() {
+        let result = std::thread::spawn(|| {
+            let alloc = GAlloc::default();
+            let _ptr = unsafe { alloc.alloc(Layout::new::<u8>()) };
+            panic!("original panic");
+        })
+        .join();
+
+        let payload = result.unwrap_err();
+        assert_eq!(Some(&"original panic"), payload.downcast_ref::<&str>());
+    }
+
+    #[test]
+    fn zero_size_policy() {
+        let layout = Layout::from_size_align(0, 1).unwrap();
+
+        let alloc = GAlloc::default().with_violation_policy(ViolationPolicy::Record);
+        unsafe { alloc.dealloc(alloc.alloc(layout), layout) };
+        assert!(!alloc.has_violations());
+
+        let alloc = GAlloc::builder(System)
+            .violation_policy(ViolationPolicy::Record)
+            .zero_size_policy(ZeroSizePolicy::Violation)
+            .build();
+        unsafe { alloc.dealloc(alloc.alloc(layout), layout) };
+        assert_eq!(
+            vec![Violation::ZeroSizeAlloc { layout }],
+            alloc.violations()
+        );
+    }
+
+    #[test]
+    fn no_lock_contention_in_single_thread() {
+        let alloc = GAlloc::default().with_violation_policy(ViolationPolicy::Record);
+        let layout = Layout::new::<u64>();
+        unsafe {
+            for i in 0..16 {
+                let ptr = alloc.alloc_tagged(layout, i);
+                let _ = alloc.allocations_with_tag(i);
+                alloc.dealloc(ptr, layout);
+            }
+            alloc.dealloc(core::ptr::null_mut(), layout);
+        }
+        let _ = alloc.history();
+        let _ = alloc.stats();
+        assert_eq!(0, alloc.lock_contention_count());
+    }
+
+    #[test]
+    fn is_shared() {
+        let alloc = GAlloc::default();
+        assert!(!alloc.is_shared());
+
+        let cloned = alloc.clone();
+        assert!(alloc.is_shared());
+        assert!(cloned.is_shared());
+
+        drop(cloned);
+        assert!(!alloc.is_shared());
+    }
+
+    #[test]
+    fn assert_disjoint() {
+        let a = GAlloc::default();
+        let b = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = a.alloc(layout);
+            GAlloc::assert_disjoint(&a, &b);
+
+            b.lock_allocatings()
+                .insert(Addr::from(ptr), Allocation::from(layout));
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                GAlloc::assert_disjoint(&a, &b)
+            }));
+            assert!(result.is_err());
+
+            b.lock_allocatings().remove(&Addr::from(ptr));
+            a.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "TestAlloc: live allocation count exceeded limit 1")]
+    fn max_live() {
+        let alloc = GAlloc::default().with_max_live(1);
+        let layout = Layout::new::<u8>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            alloc.dealloc(ptr, layout);
+            let _ptr = alloc.alloc(layout);
+            let _ptr = alloc.alloc(layout);
+        }
+    }
+
+    #[test]
+    fn alloc_count_for_layout() {
+        let alloc = GAlloc::default();
+        let l8 = Layout::new::<u64>();
+        let l4 = Layout::new::<u32>();
+        let l8_16 = Layout::from_size_align(8, 16).unwrap();
+        unsafe {
+            let p0 = alloc.alloc(l8);
+            let p1 = alloc.alloc(l8);
+            let p2 = alloc.alloc(l4);
+            assert_eq!(2, alloc.alloc_count_for_layout(l8));
+            assert_eq!(1, alloc.alloc_count_for_layout(l4));
+            assert_eq!(0, alloc.alloc_count_for_layout(l8_16));
+
+            alloc.dealloc(p0, l8);
+            assert_eq!(1, alloc.alloc_count_for_layout(l8));
+            alloc.dealloc(p1, l8);
+            alloc.dealloc(p2, l4);
+        }
+    }
+
+    #[test]
+    fn drop_policy() {
+        let layout = Layout::new::<u64>();
+        for &policy in &[DropPolicy::PrintOnLeak, DropPolicy::IgnoreOnLeak] {
+            let alloc = GAlloc::default().with_drop_policy(policy);
+            let ptr = unsafe { alloc.alloc(layout) };
+            drop(alloc);
+            unsafe { System.dealloc(ptr, layout) };
+        }
+    }
+
+    #[test]
+    fn clone_unshared() {
+        let alloc = GAlloc::default().with_violation_policy(ViolationPolicy::Record);
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+
+            let unshared = alloc.clone_unshared();
+            assert!(!GAlloc::same_allocator(&alloc, &unshared));
+            assert!(!alloc.is_shared());
+            assert_eq!(0, unshared.allocation_count());
+
+            // The configuration is inherited.
+            unshared.dealloc(ptr, layout);
+            assert_eq!(1, unshared.violations().len());
+
+            alloc.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn max_alignment_warning() {
+        let alloc = GAlloc::builder(System)
+            .violation_policy(ViolationPolicy::Record)
+            .max_alignment_warning(16)
+            .build();
+        let aligned = Layout::from_size_align(8, 16).unwrap();
+        let over_aligned = Layout::from_size_align(8, 32).unwrap();
+        unsafe {
+            alloc.dealloc(alloc.alloc(aligned), aligned);
+            assert!(!alloc.has_violations());
+
+            alloc.dealloc(alloc.alloc(over_aligned), over_aligned);
+            let expected = Violation::OverAligned {
+                layout: over_aligned,
+                max_align: 16,
+            };
+            assert_eq!(vec![expected], alloc.violations());
+        }
+    }
+
+    #[test]
+    fn alloc_sorted_addresses() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let mut ptrs: Vec<*mut u8> = (0..8).map(|_| alloc.alloc(layout)).collect();
+            let mut expected: Vec<usize> = ptrs.iter().map(|&ptr| ptr as usize).collect();
+            expected.sort_unstable();
+            assert_eq!(expected, alloc.alloc_sorted_addresses());
+
+            for ptr in ptrs.drain(..) {
+                alloc.dealloc(ptr, layout);
+            }
+        }
+        assert!(alloc.alloc_sorted_addresses().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "TestAlloc 'arena': 2 live allocations: Memory leak is detected")]
+    fn with_name() {
+        let alloc = GAlloc::default().with_name("arena");
+        let _ptrs = unsafe {
+            [
+                alloc.alloc(Layout::new::<u8>()),
+                alloc.alloc(Layout::new::<u8>()),
+            ]
+        };
+    }
+
+    #[test]
+    #[should_panic]
+    fn dealloc_null() {
+        let alloc = GAlloc::default();
+        unsafe { alloc.dealloc(core::ptr::null_mut(), Layout::new::<u8>()) };
+    }
+
+    #[test]
+    fn record_violations() {
+        let alloc = GAlloc::default().with_violation_policy(ViolationPolicy::Record);
+        assert!(!alloc.has_violations());
+
+        let layout = Layout::new::<u64>();
+        let other = Layout::new::<[u32; 2]>();
+        unsafe {
+            alloc.dealloc(core::ptr::null_mut(), layout);
+
+            let ptr = alloc.alloc(layout);
+            alloc.dealloc(ptr, other);
+            alloc.dealloc(ptr, layout);
+
+            let expected = vec![
+                Violation::NullDealloc { layout },
+                Violation::LayoutMismatch {
+                    ptr,
+                    alloc_layout: layout,
+                    dealloc_layout: other,
+                },
+                Violation::UnknownDealloc { ptr, layout },
+            ];
+            assert_eq!(expected, alloc.violations());
+        }
+    }
+
+    #[test]
+    fn record_leak() {
+        let alloc = GAlloc::default().with_violation_policy(ViolationPolicy::Record);
+        let cloned = alloc.clone();
+
+        let layout = Layout::new::<u64>();
+        let ptr = unsafe { alloc.alloc(layout) };
+
+        // Dropping the last instance does not panic.
+        drop(alloc);
+        drop(cloned);
+
+        unsafe { System.dealloc(ptr, layout) };
     }
 }
@@ -67,18 +67,66 @@
 // DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
-use crate::GAlloc;
+use crate::{GAlloc, InvalidPtrError, TestAlloc};
 use core::alloc::{GlobalAlloc, Layout};
+use core::any::Any;
 use core::cmp::Ordering;
-use core::ops::{Deref, DerefMut};
+use core::future::Future;
+use core::iter::FromIterator;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::pin::Pin;
+use core::task::{Context, Poll};
 use std::alloc::handle_alloc_error;
 use std::borrow::{Borrow, BorrowMut};
-use std::hash::{Hash, Hasher};
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io;
 
 /// Alias to `TestBox<T, GAlloc>`
 /// 'GBox' stands for 'Gharial Box'.
 pub type GBox<T> = TestBox<T, GAlloc>;
 
+// Allocates memory for `layout` , or returns a sentinel pointer aligned to `layout` without
+// calling `alloc` if the size is 0 as `std::boxed::Box` does; `GlobalAlloc` does not allow
+// zero-size allocation.
+unsafe fn alloc_or_sentinel<A: GlobalAlloc>(alloc: &A, layout: Layout) -> *mut u8 {
+    if layout.size() == 0 {
+        core::ptr::without_provenance_mut(layout.align())
+    } else {
+        alloc.alloc(layout)
+    }
+}
+
+// Returns whether `ptr` is the sentinel pointer `alloc_or_sentinel` returns for `layout` .
+// Zero-size memory passed via `from_raw_alloc` can be allocated via `alloc` instead.
+fn is_sentinel(ptr: *const u8, layout: Layout) -> bool {
+    layout.size() == 0 && ptr.addr() == layout.align()
+}
+
+// Frees `ptr` unless it is a sentinel pointer returned from `alloc_or_sentinel` .
+unsafe fn dealloc_unless_sentinel<A: GlobalAlloc>(alloc: &A, ptr: *mut u8, layout: Layout) {
+    if !is_sentinel(ptr, layout) {
+        alloc.dealloc(ptr, layout);
+    }
+}
+
+// `GlobalAlloc::realloc` for the memory returned from `alloc_or_sentinel` .
+// `new_size` must not be 0.
+unsafe fn realloc_or_alloc<A: GlobalAlloc>(
+    alloc: &A,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+) -> *mut u8 {
+    if is_sentinel(ptr, layout) {
+        alloc.alloc(Layout::from_size_align_unchecked(new_size, layout.align()))
+    } else {
+        alloc.realloc(ptr, layout, new_size)
+    }
+}
+
 /// `TestBox` behaves like `std::boxed::Box` except for it owns a reference to a `GlobalAlloc` .
 ///
 /// If template parameter `A` is [`GAlloc`] , it causes assertion error if the instance is not
@@ -89,12 +137,13 @@ pub type GBox<T> = TestBox<T, GAlloc>;
 /// For example, it sometimes requires to allocate heap memory to implement container struct,
 /// and then the elements must be dropped manually. This struct helps the test.
 ///
-#[derive(Debug)]
 pub struct TestBox<T, A>
 where
+    T: ?Sized,
     A: GlobalAlloc,
 {
     ptr: *mut T,
+    layout: Layout,
     alloc: A,
 }
 
@@ -108,6 +157,9 @@ where
     }
 }
 
+// `TryFrom<T>` can not be implemented because it conflicts with the blanket implementation
+// through this `From<T>` , whose error type is `Infallible` .
+// Use `TestBox::try_new(x, A::default())` for the fallible conversion.
 impl<T, A> From<T> for TestBox<T, A>
 where
     A: Default + GlobalAlloc,
@@ -123,6 +175,11 @@ where
 {
     /// Creates a new instance.
     ///
+    /// If `T` is a zero-sized type, `alloc` is not called and the instance holds a non-null
+    /// sentinel pointer aligned to `T` as `std::boxed::Box` does. The constructors of `TestBox`
+    /// and the drop never request zero-size memory to the allocator. Such an instance is not
+    /// [`is_dangling`] .
+    ///
     /// # Examples
     ///
     /// ```
@@ -131,15 +188,159 @@ where
     /// let alloc = GAlloc::default();
     /// let _box = TestBox::new(5, alloc);
     /// ```
+    ///
+    /// [`is_dangling`]: Self::is_dangling
     pub fn new(x: T, alloc: A) -> Self {
+        Self::new_in_layout(x, Layout::new::<T>(), alloc)
+    }
+
+    /// Creates a new instance, or returns `x` back if `alloc` fails to allocate memory.
+    ///
+    /// Unlike [`new`] , it does not call `std::alloc::handle_alloc_error` on the failure.
+    /// (`TestBox` does not implement fallible `TryFrom<T>` because it implements `From<T>` .)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{NeverAlloc, TestAlloc, TestBox};
+    ///
+    /// let alloc = TestAlloc::from(NeverAlloc);
+    /// let five = TestBox::try_new(5, alloc.clone());
+    /// assert_eq!(5, five.unwrap_err());
+    /// ```
+    ///
+    /// [`new`]: Self::new
+    pub fn try_new(x: T, alloc: A) -> Result<Self, T> {
         let layout = Layout::new::<T>();
-        let ptr = unsafe { alloc.alloc(layout) as *mut T };
+        let ptr = unsafe { alloc_or_sentinel(&alloc, layout) as *mut T };
+        if ptr.is_null() {
+            return Err(x);
+        }
+
+        unsafe { ptr.write(x) };
+        Ok(Self { ptr, layout, alloc })
+    }
+
+    /// Creates a new instance allocating the memory aligned to `align` bytes.
+    ///
+    /// It helps to test that container code handles the memory whose alignment is greater than
+    /// that of `T` .
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of 2 or less than `core::mem::align_of::<T>()` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let five = TestBox::new_at_alignment(5_u8, 64, alloc.clone());
+    /// assert_eq!(5, *five);
+    /// assert_eq!(0, &*five as *const u8 as usize % 64);
+    /// assert_eq!(64, alloc.providing_pointers()[0].1.align());
+    /// ```
+    pub fn new_at_alignment(x: T, align: usize, alloc: A) -> Self {
+        assert!(
+            core::mem::align_of::<T>() <= align,
+            "TestBox::new_at_alignment() is passed an alignment less than that of the type."
+        );
+        let layout = Layout::from_size_align(core::mem::size_of::<T>(), align).unwrap();
+        Self::new_in_layout(x, layout, alloc)
+    }
+
+    fn new_in_layout(x: T, layout: Layout, alloc: A) -> Self {
+        let ptr = unsafe { alloc_or_sentinel(&alloc, layout) as *mut T };
         if ptr.is_null() {
             handle_alloc_error(layout);
         }
 
         unsafe { ptr.write(x) };
-        Self { ptr, alloc }
+        Self { ptr, layout, alloc }
+    }
+
+    /// Creates a new instance calling `f` to initialize the memory allocated via `alloc` .
+    ///
+    /// It is useful for a type which cannot be constructed by value, e.g. a self-referential
+    /// type. If `f` panics, the memory is freed without dropping the value.
+    ///
+    /// # Safety
+    ///
+    /// `f` must initialize the value pointed to by the argument unless it panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// struct SelfRef {
+    ///     val: i32,
+    ///     this: *const SelfRef,
+    /// }
+    ///
+    /// let tb = unsafe {
+    ///     TestBox::new_in_place(GAlloc::default(), |ptr: *mut SelfRef| {
+    ///         ptr.write(SelfRef { val: 5, this: ptr });
+    ///     })
+    /// };
+    /// assert_eq!(5, tb.val);
+    /// assert_eq!(&*tb as *const SelfRef, tb.this);
+    /// ```
+    pub unsafe fn new_in_place<F>(alloc: A, f: F) -> Self
+    where
+        F: FnOnce(*mut T),
+    {
+        // Frees the memory if `f` panics.
+        struct Guard<'a, A: GlobalAlloc> {
+            ptr: *mut u8,
+            layout: Layout,
+            alloc: &'a A,
+        }
+
+        impl<A: GlobalAlloc> Drop for Guard<'_, A> {
+            fn drop(&mut self) {
+                unsafe { dealloc_unless_sentinel(self.alloc, self.ptr, self.layout) };
+            }
+        }
+
+        let layout = Layout::new::<T>();
+        let ptr = alloc_or_sentinel(&alloc, layout) as *mut T;
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        let guard = Guard {
+            ptr: ptr as *mut u8,
+            layout,
+            alloc: &alloc,
+        };
+        f(ptr);
+        core::mem::forget(guard);
+
+        Self { ptr, layout, alloc }
+    }
+
+    /// Writes `val` to `ptr` without reading or dropping the old value.
+    ///
+    /// This is a wrapper of `core::ptr::write_unaligned` .
+    ///
+    /// # Safety
+    ///
+    /// The safety requirements are the same as `core::ptr::write_unaligned` .
+    pub unsafe fn write_unaligned(ptr: *mut T, val: T) {
+        ptr.write_unaligned(val)
+    }
+
+    /// Reads the value from `ptr` without moving it.
+    ///
+    /// This is a wrapper of `core::ptr::read_unaligned` .
+    ///
+    /// # Safety
+    ///
+    /// The safety requirements are the same as `core::ptr::read_unaligned` .
+    pub unsafe fn read_unaligned(ptr: *const T) -> T {
+        ptr.read_unaligned()
     }
 
     /// Creates a new instance from raw pointer and a reference to allocator.
@@ -149,8 +350,8 @@ where
     ///
     /// # Safety
     ///
-    /// To use this function safe, the ptr should be allocated via `alloc` and it should not be
-    /// freed anywhere else.
+    /// To use this function safe, the ptr should be allocated via `alloc` with
+    /// `Layout::new::<T>()` and it should not be freed anywhere else.
     ///
     /// # Examples
     ///
@@ -173,99 +374,179 @@ where
     /// let _box = unsafe { TestBox::from_raw_alloc(ptr, alloc) };
     /// ```
     pub unsafe fn from_raw_alloc(ptr: *mut T, alloc: A) -> Self {
-        Self { ptr, alloc }
+        let layout = Layout::new::<T>();
+        Self { ptr, layout, alloc }
     }
-}
 
-impl<T, A> Clone for TestBox<T, A>
-where
-    T: Clone,
-    A: Clone + GlobalAlloc,
-{
-    fn clone(&self) -> Self {
-        Self::new(self.as_ref().clone(), self.alloc.clone())
+    /// Creates a new instance moving the value from `std::boxed::Box` .
+    ///
+    /// The value is moved into the memory newly allocated via `alloc` , and the memory of `b` is
+    /// freed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let five = TestBox::from_box(Box::new(5), alloc.clone());
+    /// assert_eq!(5, *five);
+    /// assert_eq!(1, alloc.allocation_count());
+    /// ```
+    #[allow(clippy::boxed_local)]
+    pub fn from_box(b: Box<T>, alloc: A) -> Self {
+        Self::new(*b, alloc)
     }
-}
 
-impl<T, A> Drop for TestBox<T, A>
-where
-    A: GlobalAlloc,
-{
-    fn drop(&mut self) {
-        if self.ptr.is_null() {
-            return;
-        }
+    /// Consumes `tb` and moves the value into `std::boxed::Box` .
+    ///
+    /// The memory of `tb` is freed via the allocator of `tb` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let five = TestBox::new(5, alloc.clone());
+    ///
+    /// let b: Box<i32> = TestBox::into_box(five);
+    /// assert_eq!(5, *b);
+    /// assert_eq!(0, alloc.allocation_count());
+    /// ```
+    pub fn into_box(tb: Self) -> Box<T> {
+        Box::new(Self::into_inner(tb))
+    }
 
+    fn into_inner(mut tb: Self) -> T {
         unsafe {
-            self.ptr.drop_in_place();
-            let layout = Layout::new::<T>();
-            self.alloc.dealloc(self.ptr as *mut u8, layout);
+            let val = tb.ptr.read();
+            dealloc_unless_sentinel(&tb.alloc, tb.ptr as *mut u8, tb.layout);
+            tb.ptr = core::ptr::null_mut();
+            val
         }
     }
-}
 
-impl<T, A> PartialEq<Self> for TestBox<T, A>
-where
-    T: PartialEq,
-    A: GlobalAlloc,
-{
-    fn eq(&self, rh: &Self) -> bool {
-        let l: &T = self.borrow();
-        let r: &T = rh.borrow();
-        l == r
+    /// Consumes `tb` and returns the value, or returns `T::default()` if `tb` is dangling.
+    ///
+    /// See also [`is_dangling`] .
+    ///
+    /// [`is_dangling`]: Self::is_dangling
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let tb = TestBox::new(5, alloc.clone());
+    /// assert_eq!(5, TestBox::into_inner_or_default(tb));
+    /// assert_eq!(0, alloc.allocation_count());
+    /// ```
+    pub fn into_inner_or_default(tb: Self) -> T
+    where
+        T: Default,
+    {
+        if Self::is_dangling(&tb) {
+            T::default()
+        } else {
+            Self::into_inner(tb)
+        }
     }
-}
 
-impl<T, A> Eq for TestBox<T, A>
-where
-    T: Eq,
-    A: GlobalAlloc,
-{
-}
+    /// Consumes `tb` and returns the value and the allocator.
+    ///
+    /// `replacement` is written into the allocation and dropped there before the memory is freed.
+    /// It helps to test the destructor of `T` running in the memory of `A` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let tb = TestBox::new(5, GAlloc::default());
+    /// let (five, alloc) = TestBox::take(tb, 6);
+    /// assert_eq!(5, five);
+    /// assert_eq!(0, alloc.allocation_count());
+    /// ```
+    pub fn take(tb: Self, replacement: T) -> (T, A) {
+        let (ptr, layout, alloc) = Self::into_raw_parts(tb);
+        unsafe {
+            let val = ptr.read();
+            ptr.write(replacement);
+            ptr.drop_in_place();
+            dealloc_unless_sentinel(&alloc, ptr as *mut u8, layout);
+            (val, alloc)
+        }
+    }
 
-impl<T, A> PartialOrd<Self> for TestBox<T, A>
-where
-    T: PartialOrd,
-    A: GlobalAlloc,
-{
-    fn partial_cmp(&self, rh: &Self) -> Option<Ordering> {
-        let l: &T = self.borrow();
-        let r: &T = rh.borrow();
-        l.partial_cmp(r)
+    /// Consumes `tb` and returns the value and the allocator.
+    ///
+    /// This is the same as `TestBox::take(tb, T::default())` .
+    /// See also [`take`] .
+    ///
+    /// [`take`]: Self::take
+    pub fn take_with_default(tb: Self) -> (T, A)
+    where
+        T: Default,
+    {
+        Self::take(tb, T::default())
     }
-}
 
-impl<T, A> Ord for TestBox<T, A>
-where
-    T: Ord,
-    A: GlobalAlloc,
-{
-    fn cmp(&self, rh: &Self) -> Ordering {
-        let l: &T = self.borrow();
-        let r: &T = rh.borrow();
-        l.cmp(r)
+    /// Replaces the value with `val` without any allocation nor deallocation.
+    ///
+    /// The old value is dropped after `val` is installed, so `tb` owns a valid value even if
+    /// the destructor of the old value panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let mut tb = TestBox::new(String::from("foo"), alloc.clone());
+    /// let before = alloc.stats();
+    ///
+    /// TestBox::write(&mut tb, String::from("bar"));
+    /// assert_eq!("bar", tb.as_str());
+    ///
+    /// // The memory of `TestBox` is reused.
+    /// assert_eq!(before, alloc.stats());
+    /// ```
+    pub fn write(tb: &mut Self, val: T) {
+        drop(core::mem::replace(&mut **tb, val));
     }
-}
 
-impl<T, A> Hash for TestBox<T, A>
-where
-    T: Hash,
-    A: GlobalAlloc,
-{
-    fn hash<H>(&self, state: &mut H)
+    /// Moves the value of `tb` into a new instance allocated via `new_alloc` .
+    ///
+    /// The memory of `tb` is freed through its allocator before the new allocation. The value is
+    /// moved rather than cloned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let from = GAlloc::default();
+    /// let to = GAlloc::default();
+    ///
+    /// let five = TestBox::new(5, from.clone());
+    /// let five = TestBox::replace_alloc(five, to.clone());
+    /// assert_eq!(5, *five);
+    /// assert_eq!(0, from.allocation_count());
+    /// assert_eq!(1, to.allocation_count());
+    /// ```
+    pub fn replace_alloc<B>(tb: Self, new_alloc: B) -> TestBox<T, B>
     where
-        H: Hasher,
+        B: GlobalAlloc,
     {
-        let t: &T = self.borrow();
-        t.hash(state)
+        TestBox::new(Self::into_inner(tb), new_alloc)
     }
-}
 
-impl<T, A> TestBox<T, A>
-where
-    A: GlobalAlloc,
-{
-    /// Consumes and leaks `TestBox` .
+    /// Merges the values of `a` and `b` by `f` into a new instance allocated via the allocator
+    /// of `a` .
+    ///
+    /// The memory of `a` and `b` is freed before `f` is called.
     ///
     /// # Examples
     ///
@@ -273,97 +554,1064 @@ where
     /// use gharial::{GAlloc, TestBox};
     ///
     /// let alloc = GAlloc::default();
+    /// let a = TestBox::new(vec![1, 2], alloc.clone());
+    /// let b = TestBox::new(vec![3], alloc.clone());
     ///
-    /// let five = TestBox::new(5, alloc.clone());
-    /// let leaked = TestBox::leak(five);
-    /// assert_eq!(5, *leaked);
-    ///
-    /// let five_ = unsafe { TestBox::from_raw_alloc(leaked, alloc) };
+    /// let merged = TestBox::combine(a, b, |mut a, b| {
+    ///     a.extend(b);
+    ///     a
+    /// });
+    /// assert_eq!(vec![1, 2, 3], *merged);
+    /// assert_eq!(1, alloc.allocation_count());
     /// ```
-    pub fn leak<'a>(mut tb: Self) -> &'a mut T
+    pub fn combine<U, F>(a: Self, b: Self, f: F) -> TestBox<U, A>
     where
-        T: 'a,
+        F: FnOnce(T, T) -> U,
     {
-        let ptr = tb.ptr;
-        tb.ptr = core::ptr::null_mut();
+        let (ptr, layout, alloc) = Self::into_raw_parts(a);
+        let a = unsafe {
+            let val = ptr.read();
+            dealloc_unless_sentinel(&alloc, ptr as *mut u8, layout);
+            val
+        };
+        let b = Self::into_inner(b);
 
-        unsafe { &mut *ptr }
+        TestBox::new(f(a, b), alloc)
     }
 
-    /// Consumes the `TestBox` and returning a wrapped raw pointer.
+    /// Returns the slice of `len` elements starting from the value of `tb` .
+    ///
+    /// It is useful when `tb` is allocated with space for multiple `T` ; e.g. via
+    /// [`new_with_layout`] .
+    ///
+    /// # Safety
+    ///
+    /// All the `len` elements must be initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tb` is dangling, or if the allocation is not large enough for `len` elements.
     ///
     /// # Examples
     ///
     /// ```
     /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::Layout;
     ///
-    /// let alloc = GAlloc::default();
+    /// let layout = Layout::array::<u32>(3).unwrap();
+    /// let tb = unsafe { TestBox::new_with_layout(layout, GAlloc::default()) };
     ///
-    /// let five = TestBox::new(5, alloc.clone());
-    /// let raw = TestBox::into_raw(five);
-    /// assert_eq!(5, unsafe { *raw });
+    /// let (ptr, layout, alloc) = TestBox::into_raw_parts(tb);
+    /// let ptr = ptr as *mut u32;
+    /// let tb = unsafe {
+    ///     for i in 0..3 {
+    ///         ptr.add(i).write(i as u32);
+    ///     }
+    ///     TestBox::from_raw_parts(ptr, layout, alloc)
+    /// };
     ///
-    /// let five_ = unsafe { TestBox::from_raw_alloc(raw, alloc) };
+    /// assert_eq!(&[0, 1, 2], unsafe { TestBox::as_slice(&tb, 3) });
     /// ```
-    pub fn into_raw(mut tb: Self) -> *mut T {
-        let ptr = tb.ptr;
-        tb.ptr = core::ptr::null_mut();
-        ptr
+    ///
+    /// [`new_with_layout`]: TestBox::new_with_layout
+    pub unsafe fn as_slice(tb: &Self, len: usize) -> &[T] {
+        assert!(
+            !Self::is_dangling(tb),
+            "TestBox::as_slice() is passed a dangling box."
+        );
+        assert!(
+            core::mem::size_of::<T>().saturating_mul(len) <= tb.layout.size(),
+            "TestBox::as_slice() is passed a length greater than the allocation."
+        );
+        core::slice::from_raw_parts(tb.ptr, len)
     }
-}
 
-impl<T, A> AsRef<T> for TestBox<T, A>
-where
-    A: GlobalAlloc,
-{
-    fn as_ref(&self) -> &T {
-        &*self
-    }
+    /// Reallocates the memory of `tb` to `size_of::<T>() + extra_bytes` bytes keeping the
+    /// value, and returns a new instance pointing to the memory.
+    ///
+    /// It helps to test an over-sized node; a header struct followed by variable length data.
+    /// The extra bytes are not initialized and can be accessed only via the raw pointer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tb` is dangling, or if `size_of::<T>() + extra_bytes` is 0 or overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{assert_exact_allocations, GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let header = TestBox::new(3_u32, alloc.clone());
+    ///
+    /// let mut node = TestBox::allocate_extra(header, 3);
+    /// unsafe {
+    ///     let data = TestBox::as_mut_ptr(&mut node).add(1) as *mut u8;
+    ///     data.write_bytes(7, 3);
+    ///     assert_eq!(7, *data.add(2));
+    /// }
+    /// assert_eq!(3, *node);
+    /// assert_exact_allocations!(alloc, [(7, 4)]);
+    /// ```
+    pub fn allocate_extra(tb: Self, extra_bytes: usize) -> Self {
+        assert!(
+            !Self::is_dangling(&tb),
+            "TestBox::allocate_extra() is passed a dangling box."
+        );
+        let size = core::mem::size_of::<T>()
+            .checked_add(extra_bytes)
+            .expect("TestBox::allocate_extra() overflows the size.");
+        assert_ne!(
+            0, size,
+            "TestBox::allocate_extra() cannot allocate 0 bytes."
+        );
+
+        let layout = Layout::from_size_align(size, tb.layout.align()).unwrap();
+        let (ptr, old_layout, alloc) = Self::into_raw_parts(tb);
+        let ptr = unsafe { realloc_or_alloc(&alloc, ptr as *mut u8, old_layout, size) as *mut T };
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        Self { ptr, layout, alloc }
+    }
+}
+
+impl<A> TestBox<(), A>
+where
+    A: GlobalAlloc,
+{
+    /// Creates a new instance of the unit type without any allocation.
+    ///
+    /// This is the same as `TestBox::new((), alloc)` . See also [`new`] .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let unit = TestBox::new_zst(alloc.clone());
+    /// assert!(!TestBox::is_dangling(&unit));
+    /// assert_eq!(0, alloc.stats().total_alloc_count);
+    /// ```
+    ///
+    /// [`new`]: TestBox::new
+    pub fn new_zst(alloc: A) -> Self {
+        Self::new((), alloc)
+    }
+}
+
+impl<A> TestBox<MaybeUninit<u8>, A>
+where
+    A: GlobalAlloc,
+{
+    /// Allocates memory with `layout` via `alloc` , and creates a new instance pointing to the
+    /// first byte.
+    ///
+    /// The memory is freed with `layout` when the instance is dropped. It is useful when the
+    /// type of the value is not known at the allocation; e.g. to test heterogeneous arena.
+    ///
+    /// # Safety
+    ///
+    /// The memory is not initialized. The caller is responsible for writing the data through
+    /// the pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{assert_exact_allocations, GAlloc, TestBox};
+    /// use std::alloc::Layout;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let layout = Layout::from_size_align(24, 8).unwrap();
+    ///
+    /// let mut tb = unsafe { TestBox::new_with_layout(layout, alloc.clone()) };
+    /// unsafe { (TestBox::as_mut_ptr(&mut tb) as *mut [u64; 3]).write([1, 2, 3]) };
+    /// assert_exact_allocations!(alloc, [(24, 8)]);
+    /// ```
+    pub unsafe fn new_with_layout(layout: Layout, alloc: A) -> Self {
+        let ptr = alloc_or_sentinel(&alloc, layout) as *mut MaybeUninit<u8>;
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+        Self { ptr, layout, alloc }
+    }
+}
+
+impl<A> TestBox<dyn Any, A>
+where
+    A: GlobalAlloc,
+{
+    /// Converts `tb` into `TestBox<U, A>` if the value is `U` , or returns `tb` back.
+    ///
+    /// The memory is not re-allocated. Use [`into_dyn!`] to make `TestBox<dyn Any, A>` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{into_dyn, GAlloc, GBox, TestBox};
+    /// use std::any::Any;
+    ///
+    /// let tb = GBox::new(5_i32, GAlloc::default());
+    /// let tb: TestBox<dyn Any, GAlloc> = into_dyn!(tb => dyn Any);
+    ///
+    /// let tb = TestBox::downcast::<String>(tb).unwrap_err();
+    /// let five = TestBox::downcast::<i32>(tb).unwrap();
+    /// assert_eq!(5, *five);
+    /// ```
+    ///
+    /// [`into_dyn!`]: crate::into_dyn
+    pub fn downcast<U>(tb: Self) -> Result<TestBox<U, A>, Self>
+    where
+        U: Any,
+    {
+        if (*tb).is::<U>() {
+            let (ptr, layout, alloc) = Self::into_raw_parts(tb);
+            Ok(unsafe { TestBox::from_raw_parts(ptr as *mut U, layout, alloc) })
+        } else {
+            Err(tb)
+        }
+    }
+}
+
+impl<T, A> TestBox<[T], A>
+where
+    T: Clone,
+    A: GlobalAlloc,
+{
+    /// Creates a new slice of `len` elements cloned from `val` .
+    ///
+    /// The memory is allocated with `Layout::array::<T>(len)` . If cloning panics, the cloned
+    /// elements are dropped and the memory is freed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::Layout;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let tb = TestBox::new_array(3, "a".to_string(), alloc.clone());
+    /// assert_eq!(&["a", "a", "a"], &*tb);
+    /// assert_eq!(Layout::array::<String>(3).unwrap(), alloc.providing_pointers()[0].1);
+    /// ```
+    pub fn new_array(len: usize, val: T, alloc: A) -> Self {
+        // Drops the written elements and frees the memory if `T::clone` panics.
+        struct Guard<'a, T, A: GlobalAlloc> {
+            ptr: *mut T,
+            len: usize,
+            layout: Layout,
+            alloc: &'a A,
+        }
+
+        impl<T, A: GlobalAlloc> Drop for Guard<'_, T, A> {
+            fn drop(&mut self) {
+                unsafe {
+                    core::ptr::slice_from_raw_parts_mut(self.ptr, self.len).drop_in_place();
+                    dealloc_unless_sentinel(self.alloc, self.ptr as *mut u8, self.layout);
+                }
+            }
+        }
+
+        let layout = Layout::array::<T>(len).unwrap();
+        let ptr = unsafe { alloc_or_sentinel(&alloc, layout) as *mut T };
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        let mut guard = Guard {
+            ptr,
+            len: 0,
+            layout,
+            alloc: &alloc,
+        };
+        if 0 < len {
+            for i in 1..len {
+                unsafe { ptr.add(i - 1).write(val.clone()) };
+                guard.len = i;
+            }
+            unsafe { ptr.add(len - 1).write(val) };
+        }
+        core::mem::forget(guard);
+
+        let ptr = core::ptr::slice_from_raw_parts_mut(ptr, len);
+        Self { ptr, layout, alloc }
+    }
+}
+
+impl<T, A> TestBox<[T], A>
+where
+    A: Clone + GlobalAlloc,
+{
+    /// Moves the elements of `tb` into 2 newly allocated slices at `idx` , and frees `tb` .
+    ///
+    /// The first one contains `[0, idx)` and the second one contains `[idx, len)` .
+    /// Both of them use the same allocator as `tb` .
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is greater than the length of `tb` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{into_dyn, GAlloc, GBox, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let tb: TestBox<[i32], GAlloc> = into_dyn!(GBox::new([1, 2, 3], alloc.clone()) => [i32]);
+    ///
+    /// let (left, right) = TestBox::split_at(tb, 1);
+    /// assert_eq!(&[1], &*left);
+    /// assert_eq!(&[2, 3], &*right);
+    /// assert_eq!(2, alloc.allocation_count());
+    /// ```
+    pub fn split_at(tb: Self, idx: usize) -> (Self, Self) {
+        let len = tb.len();
+        assert!(idx <= len, "mid > len");
+
+        let (ptr, layout, alloc) = Self::into_raw_parts(tb);
+        let ptr = ptr as *mut T;
+        unsafe {
+            let left = Self::copy_from_raw(ptr, idx, alloc.clone());
+            let right = Self::copy_from_raw(ptr.add(idx), len - idx, alloc.clone());
+            dealloc_unless_sentinel(&alloc, ptr as *mut u8, layout);
+            (left, right)
+        }
+    }
+
+    // Allocates a new slice and moves `len` elements from `src` into it.
+    unsafe fn copy_from_raw(src: *const T, len: usize, alloc: A) -> Self {
+        let layout = Layout::array::<T>(len).unwrap();
+        let ptr = alloc_or_sentinel(&alloc, layout) as *mut T;
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        core::ptr::copy_nonoverlapping(src, ptr, len);
+        let ptr = core::ptr::slice_from_raw_parts_mut(ptr, len);
+        Self { ptr, layout, alloc }
+    }
+}
+
+impl<T, A> Clone for TestBox<T, A>
+where
+    T: Clone,
+    A: Clone + GlobalAlloc,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.as_ref().clone(), self.alloc.clone())
+    }
+}
+
+impl<T, A, S> TestBox<T, TestAlloc<A, S>>
+where
+    A: Clone + GlobalAlloc,
+    S: BuildHasher,
+{
+    /// Creates a new instance from raw pointer and a clone of `alloc` after checking that `ptr`
+    /// is being allocated via `alloc` with `Layout::new::<T>()` .
+    ///
+    /// Returns `Err` if the check fails.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should point to an initialized `T` and it should not be freed anywhere else.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let ptr = unsafe {
+    ///     let ptr = alloc.alloc(Layout::new::<i32>()) as *mut i32;
+    ///     ptr.write(5);
+    ///     ptr
+    /// };
+    ///
+    /// let tb = unsafe { TestBox::try_from_raw_alloc(ptr, &alloc) }.unwrap();
+    /// assert_eq!(5, *tb);
+    ///
+    /// let err = unsafe { TestBox::<i32, GAlloc>::try_from_raw_alloc(ptr.wrapping_add(1), &alloc) };
+    /// assert!(err.is_err());
+    /// ```
+    pub unsafe fn try_from_raw_alloc(
+        ptr: *mut T,
+        alloc: &TestAlloc<A, S>,
+    ) -> Result<Self, InvalidPtrError> {
+        let expected_layout = Layout::new::<T>();
+        let layout = alloc.layout_of(ptr as *mut u8);
+        let valid = layout == Some(expected_layout)
+            || (layout.is_none() && is_sentinel(ptr as *const u8, expected_layout));
+        if valid {
+            Ok(Self::from_raw_alloc(ptr, alloc.clone()))
+        } else {
+            Err(InvalidPtrError {
+                ptr: ptr as usize,
+                expected_layout,
+            })
+        }
+    }
+}
+
+impl<T, A, S> TestBox<T, TestAlloc<A, S>>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    /// Returns a reference to the allocator of `tb` .
+    ///
+    /// It is a shortcut to check the allocator that `tb` is created with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let tb = TestBox::new(5, GAlloc::default());
+    /// assert_eq!(1, TestBox::get_alloc_ref(&tb).allocation_count());
+    /// ```
+    pub fn get_alloc_ref(tb: &Self) -> &TestAlloc<A, S> {
+        &tb.alloc
+    }
+}
+
+impl<T, A> TestBox<T, A>
+where
+    T: Clone,
+    A: Clone + GlobalAlloc,
+{
+    /// Returns a mutable reference to the value.
+    ///
+    /// `TestBox` always owns the value uniquely, so unlike `std::borrow::Cow::to_mut` , it never
+    /// clones the value.
+    pub fn cow_mut(tb: &mut Self) -> &mut T {
+        tb
+    }
+}
+
+impl<T, A> TestBox<T, A>
+where
+    T: Clone,
+    A: GlobalAlloc,
+{
+    /// Clones the value of `tb` into `target` reusing the allocation of `target` like
+    /// `Clone::clone_from` .
+    ///
+    /// Unlike `target = tb.clone()` , it neither allocates nor deallocates memory, and the
+    /// allocator of `target` is not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let five = TestBox::new(5, alloc.clone());
+    /// let mut target = TestBox::new(0, alloc.clone());
+    ///
+    /// let before = alloc.stats();
+    /// TestBox::clone_into(&five, &mut target);
+    /// assert_eq!(5, *target);
+    /// assert_eq!(before, alloc.stats());
+    /// ```
+    pub fn clone_into(tb: &Self, target: &mut Self) {
+        (**target).clone_from(&**tb);
+    }
+
+    /// Clones the value into a new instance allocated via `alloc` .
+    ///
+    /// `Clone::clone` uses `A::clone()` , which shares the tracking information if `A` is
+    /// [`TestAlloc`] . The returned value of this function is tracked by `alloc` independently
+    /// of `tb` ; e.g. pass a new `TestAlloc` to check the leaks of the clone separately.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let five = TestBox::new(5, alloc.clone());
+    ///
+    /// let other = GAlloc::default();
+    /// let fresh = TestBox::clone_into_fresh(&five, other.clone());
+    /// assert_eq!(5, *fresh);
+    /// assert_eq!(1, alloc.allocation_count());
+    /// assert_eq!(1, other.allocation_count());
+    /// ```
+    ///
+    /// [`TestAlloc`]: crate::TestAlloc
+    pub fn clone_into_fresh(tb: &Self, alloc: A) -> Self {
+        Self::new((**tb).clone(), alloc)
+    }
+}
+
+impl<T, A> Drop for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn drop(&mut self) {
+        if self.ptr.is_null() {
+            return;
+        }
+
+        unsafe {
+            self.ptr.drop_in_place();
+            dealloc_unless_sentinel(&self.alloc, self.ptr as *mut u8, self.layout);
+        }
+    }
+}
+
+impl<T, A> PartialEq<Self> for TestBox<T, A>
+where
+    T: ?Sized + PartialEq,
+    A: GlobalAlloc,
+{
+    fn eq(&self, rh: &Self) -> bool {
+        let l: &T = self.borrow();
+        let r: &T = rh.borrow();
+        l == r
+    }
+}
+
+// `PartialEq<TestBox<T, A>> for T` can not be implemented because of the orphan rule.
+impl<T, A> PartialEq<T> for TestBox<T, A>
+where
+    T: PartialEq,
+    A: GlobalAlloc,
+{
+    fn eq(&self, rh: &T) -> bool {
+        let l: &T = self.borrow();
+        l == rh
+    }
+}
+
+// `PartialEq<TestBox<T, A>> for Box<T>` can not be implemented because of the orphan rule;
+// `Box` is `#[fundamental]` , so `T` in `Box<T>` is not covered.
+impl<T, A> PartialEq<Box<T>> for TestBox<T, A>
+where
+    T: ?Sized + PartialEq,
+    A: GlobalAlloc,
+{
+    fn eq(&self, rh: &Box<T>) -> bool {
+        **self == **rh
+    }
+}
+
+impl<T, A> Eq for TestBox<T, A>
+where
+    T: ?Sized + Eq,
+    A: GlobalAlloc,
+{
+}
+
+impl<T, A> PartialOrd<Self> for TestBox<T, A>
+where
+    T: ?Sized + PartialOrd,
+    A: GlobalAlloc,
+{
+    fn partial_cmp(&self, rh: &Self) -> Option<Ordering> {
+        let l: &T = self.borrow();
+        let r: &T = rh.borrow();
+        l.partial_cmp(r)
+    }
+}
+
+impl<T, A> PartialOrd<T> for TestBox<T, A>
+where
+    T: PartialOrd,
+    A: GlobalAlloc,
+{
+    fn partial_cmp(&self, rh: &T) -> Option<Ordering> {
+        let l: &T = self.borrow();
+        l.partial_cmp(rh)
+    }
+}
+
+impl<T, A> Ord for TestBox<T, A>
+where
+    T: ?Sized + Ord,
+    A: GlobalAlloc,
+{
+    fn cmp(&self, rh: &Self) -> Ordering {
+        let l: &T = self.borrow();
+        let r: &T = rh.borrow();
+        l.cmp(r)
+    }
+}
+
+impl<T, A> Hash for TestBox<T, A>
+where
+    T: ?Sized + Hash,
+    A: GlobalAlloc,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        let t: &T = self.borrow();
+        t.hash(state)
+    }
+}
+
+impl<T, A> TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    /// Returns a mutable reference to the value like `std::sync::Arc::get_mut` .
+    ///
+    /// `TestBox` always owns the value uniquely, so it returns `Some` unless `tb` is dangling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let mut tb = TestBox::new(5, GAlloc::default());
+    /// *TestBox::get_mut(&mut tb).unwrap() = 6;
+    /// assert_eq!(6, *tb);
+    /// ```
+    pub fn get_mut(tb: &mut Self) -> Option<&mut T> {
+        unsafe { tb.ptr.as_mut() }
+    }
+
+    /// Fills the whole allocation of `tb` with `val` without dropping the value.
+    ///
+    /// # Safety
+    ///
+    /// `tb` must not be dangling. The filled bytes must be a valid value of `T` ; otherwise,
+    /// using or dropping `tb` is undefined behavior.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let mut tb = TestBox::new(0_u32, GAlloc::default());
+    /// unsafe { TestBox::write_bytes(&mut tb, 0xff) };
+    /// assert_eq!(u32::MAX, *tb);
+    /// ```
+    pub unsafe fn write_bytes(tb: &mut Self, val: u8) {
+        (tb.ptr as *mut u8).write_bytes(val, tb.layout.size());
+    }
+
+    /// Returns the raw pointer to the allocation without creating any reference.
+    ///
+    /// Unlike `&mut *tb as *mut T` , the returned pointer can access the whole allocation; e.g.
+    /// that made by [`new_with_layout`] .
+    ///
+    /// [`new_with_layout`]: TestBox::new_with_layout
+    pub fn as_mut_ptr(tb: &mut Self) -> *mut T {
+        tb.ptr
+    }
+
+    /// Reallocates the memory of `tb` to `new_layout` via `GlobalAlloc::realloc` , and returns
+    /// a new instance pointing to the resized memory, or returns `tb` back if the allocator
+    /// fails.
+    ///
+    /// The bytes are preserved up to the smaller size, however, the value is neither dropped
+    /// nor typed any more. It is the primitive to implement such as `Vec::reserve` .
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tb` is dangling, or if the size of `new_layout` is 0, or if the alignment of
+    /// `new_layout` is different from that of the current allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{assert_exact_allocations, GAlloc, TestBox};
+    /// use std::alloc::Layout;
+    ///
+    /// let alloc = GAlloc::default();
+    /// let tb = TestBox::new([1_u32, 2], alloc.clone());
+    ///
+    /// let layout = Layout::array::<u32>(4).unwrap();
+    /// let mut resized = TestBox::try_resize(tb, layout).unwrap();
+    /// let ptr = TestBox::as_mut_ptr(&mut resized) as *mut u32;
+    /// assert_eq!([1, 2], unsafe { *(ptr as *const [u32; 2]) });
+    /// assert_exact_allocations!(alloc, [(16, 4)]);
+    /// ```
+    pub fn try_resize(tb: Self, new_layout: Layout) -> Result<TestBox<MaybeUninit<u8>, A>, Self> {
+        assert!(
+            !Self::is_dangling(&tb),
+            "TestBox::try_resize() is passed a dangling box."
+        );
+        assert_ne!(
+            0,
+            new_layout.size(),
+            "TestBox::try_resize() cannot resize to 0 bytes."
+        );
+        assert_eq!(
+            tb.layout.align(),
+            new_layout.align(),
+            "TestBox::try_resize() cannot change the alignment."
+        );
+
+        let ptr =
+            unsafe { realloc_or_alloc(&tb.alloc, tb.ptr as *mut u8, tb.layout, new_layout.size()) };
+        if ptr.is_null() {
+            return Err(tb);
+        }
+
+        let (_, _, alloc) = Self::into_raw_parts(tb);
+        Ok(TestBox {
+            ptr: ptr as *mut MaybeUninit<u8>,
+            layout: new_layout,
+            alloc,
+        })
+    }
+
+    // Returns the address of the allocation. It is 0 if `tb` is dangling.
+    pub(crate) fn addr(tb: &Self) -> usize {
+        tb.ptr.addr()
+    }
+
+    /// Returns true if `tb` does not own any value, or false.
+    ///
+    /// `TestBox` is dangling after the value is moved out by such as [`leak`] or [`into_raw`] .
+    /// Dereferencing a dangling `TestBox` panics.
+    ///
+    /// [`leak`]: Self::leak
+    /// [`into_raw`]: Self::into_raw
+    pub fn is_dangling(tb: &Self) -> bool {
+        tb.ptr.is_null()
+    }
+
+    /// Decomposes `tb` into the raw pointer, the layout of the allocation, and the allocator
+    /// without dropping the value nor freeing the memory.
+    ///
+    /// See also [`from_raw_parts`] and [`into_dyn!`] .
+    ///
+    /// [`from_raw_parts`]: Self::from_raw_parts
+    /// [`into_dyn!`]: crate::into_dyn
+    pub fn into_raw_parts(tb: Self) -> (*mut T, Layout, A) {
+        let tb = ManuallyDrop::new(tb);
+        let alloc = unsafe { core::ptr::read(&tb.alloc) };
+        (tb.ptr, tb.layout, alloc)
+    }
+
+    /// Creates a new instance from the raw parts that [`into_raw_parts`] returns.
+    ///
+    /// Unlike [`from_raw_alloc`] , `T` can be unsized; e.g. `TestBox<dyn Error, A>` can be
+    /// made of the raw parts of `TestBox<io::Error, A>` .
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should point to an initialized value, and it should be allocated via `alloc` with
+    /// `layout` . It should not be freed anywhere else.
+    ///
+    /// [`into_raw_parts`]: Self::into_raw_parts
+    /// [`from_raw_alloc`]: Self::from_raw_alloc
+    pub unsafe fn from_raw_parts(ptr: *mut T, layout: Layout, alloc: A) -> Self {
+        Self { ptr, layout, alloc }
+    }
+
+    /// Consumes and leaks `TestBox` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    ///
+    /// let five = TestBox::new(5, alloc.clone());
+    /// let leaked = TestBox::leak(five);
+    /// assert_eq!(5, *leaked);
+    ///
+    /// let five_ = unsafe { TestBox::from_raw_alloc(leaked, alloc) };
+    /// ```
+    pub fn leak<'a>(mut tb: Self) -> &'a mut T
+    where
+        T: 'a,
+    {
+        let ptr = tb.ptr;
+        tb.ptr = ptr.with_addr(0);
+
+        unsafe { &mut *ptr }
+    }
+
+    /// Consumes the `TestBox` and returning a wrapped raw pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    ///
+    /// let five = TestBox::new(5, alloc.clone());
+    /// let raw = TestBox::into_raw(five);
+    /// assert_eq!(5, unsafe { *raw });
+    ///
+    /// let five_ = unsafe { TestBox::from_raw_alloc(raw, alloc) };
+    /// ```
+    pub fn into_raw(mut tb: Self) -> *mut T {
+        let ptr = tb.ptr;
+        tb.ptr = ptr.with_addr(0);
+        ptr
+    }
+
+    /// Converts `TestBox<T, A>` into `Pin<TestBox<T, A>>` .
+    ///
+    /// `TestBox` implements `Future` only if `T` is `Unpin` . Use this function to poll a future
+    /// which is not `Unpin` .
+    ///
+    /// This is safe because the value in the heap is never moved until `TestBox` is dropped, and
+    /// `Pin` does not provide any way to move the value out of `TestBox` .
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::GBox;
+    /// use std::future::Future;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// let mut fut = GBox::into_pin(GBox::from(async { 5 }));
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert_eq!(Poll::Ready(5), fut.as_mut().poll(&mut cx));
+    /// ```
+    pub fn into_pin(tb: Self) -> Pin<Self> {
+        unsafe { Pin::new_unchecked(tb) }
+    }
+}
+
+impl<T, A> AsRef<T> for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T, A> AsMut<T> for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn as_mut(&mut self) -> &mut T {
+        &mut *self
+    }
+}
+
+impl<T, A> Borrow<T> for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, A> BorrowMut<T> for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn borrow_mut(&mut self) -> &mut T {
+        &mut *self
+    }
+}
+
+impl<T, A> Deref for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    type Target = T;
+    fn deref(&self) -> &T {
+        if Self::is_dangling(self) {
+            panic!("attempt to dereference a dangling TestBox");
+        }
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T, A> DerefMut for TestBox<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        if Self::is_dangling(self) {
+            panic!("attempt to dereference a dangling TestBox");
+        }
+        unsafe { &mut *self.ptr }
+    }
+}
+
+impl<T, A, Idx> Index<Idx> for TestBox<T, A>
+where
+    T: ?Sized + Index<Idx>,
+    A: GlobalAlloc,
+{
+    type Output = T::Output;
+
+    fn index(&self, index: Idx) -> &Self::Output {
+        &(**self)[index]
+    }
+}
+
+impl<T, A, Idx> IndexMut<Idx> for TestBox<T, A>
+where
+    T: ?Sized + IndexMut<Idx>,
+    A: GlobalAlloc,
+{
+    fn index_mut(&mut self, index: Idx) -> &mut Self::Output {
+        &mut (**self)[index]
+    }
+}
+
+impl<T, A> io::Write for TestBox<T, A>
+where
+    T: ?Sized + io::Write,
+    A: GlobalAlloc,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+impl<T, A> io::Read for TestBox<T, A>
+where
+    T: ?Sized + io::Read,
+    A: GlobalAlloc,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read(buf)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        (**self).read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        (**self).read_exact(buf)
+    }
 }
 
-impl<T, A> AsMut<T> for TestBox<T, A>
+impl<T, A> io::BufRead for TestBox<T, A>
 where
+    T: ?Sized + io::BufRead,
     A: GlobalAlloc,
 {
-    fn as_mut(&mut self) -> &mut T {
-        &mut *self
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        (**self).fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        (**self).consume(amt)
+    }
+
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_until(byte, buf)
+    }
+
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        (**self).read_line(buf)
     }
 }
 
-impl<T, A> Borrow<T> for TestBox<T, A>
+// There is no `TestVec` ; `TestBox` delegates to the container it holds instead.
+impl<T, A, U> Extend<U> for TestBox<T, A>
 where
+    T: Extend<U>,
     A: GlobalAlloc,
 {
-    fn borrow(&self) -> &T {
-        &*self
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = U>,
+    {
+        (**self).extend(iter)
     }
 }
 
-impl<T, A> BorrowMut<T> for TestBox<T, A>
+impl<T, A, U> FromIterator<U> for TestBox<T, A>
+where
+    T: FromIterator<U>,
+    A: Default + GlobalAlloc,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = U>,
+    {
+        Self::from(T::from_iter(iter))
+    }
+}
+
+// Like `std::boxed::Box` , pinning `TestBox` does not pin the heap value.
+impl<T, A> Unpin for TestBox<T, A>
 where
+    T: ?Sized,
     A: GlobalAlloc,
 {
-    fn borrow_mut(&mut self) -> &mut T {
-        &mut *self
+}
+
+impl<T, A> Future for TestBox<T, A>
+where
+    T: ?Sized + Future + Unpin,
+    A: GlobalAlloc,
+{
+    type Output = T::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut **self).poll(cx)
     }
 }
 
-impl<T, A> Deref for TestBox<T, A>
+impl<T, A> fmt::Debug for TestBox<T, A>
 where
+    T: ?Sized + fmt::Debug,
     A: GlobalAlloc,
 {
-    type Target = T;
-    fn deref(&self) -> &T {
-        unsafe { &*self.ptr }
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
     }
 }
 
-impl<T, A> DerefMut for TestBox<T, A>
+impl<T, A> fmt::Display for TestBox<T, A>
 where
+    T: ?Sized + fmt::Display,
     A: GlobalAlloc,
 {
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.ptr }
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T, A> Error for TestBox<T, A>
+where
+    T: ?Sized + Error,
+    A: GlobalAlloc,
+{
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        (**self).description()
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        (**self).source()
     }
 }
 
@@ -429,4 +1677,541 @@ mod tests {
         let tb = GBox::from(35);
         let _cloned = tb.clone();
     }
+
+    #[test]
+    fn write() {
+        use std::io::Write;
+
+        let mut tb = GBox::from(Vec::<u8>::new());
+        tb.write_all(b"foo").unwrap();
+        assert_eq!(3, tb.write(b"bar").unwrap());
+        tb.flush().unwrap();
+        assert_eq!(b"foobar", tb.as_slice());
+    }
+
+    #[test]
+    fn read() {
+        use std::io::{BufRead, Cursor, Read};
+
+        let mut tb = GBox::from(Cursor::new(b"foo\nbar".to_vec()));
+
+        let mut line = String::new();
+        assert_eq!(4, tb.read_line(&mut line).unwrap());
+        assert_eq!("foo\n", line);
+
+        let mut rest = Vec::new();
+        assert_eq!(3, tb.read_to_end(&mut rest).unwrap());
+        assert_eq!(b"bar", rest.as_slice());
+    }
+
+    #[test]
+    fn future() {
+        use std::task::Waker;
+
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut tb = GBox::from(core::future::ready(35));
+        assert_eq!(Poll::Ready(35), Pin::new(&mut tb).poll(&mut cx));
+
+        let mut pinned = GBox::into_pin(GBox::from(async { 35 }));
+        assert_eq!(Poll::Ready(35), pinned.as_mut().poll(&mut cx));
+    }
+
+    #[test]
+    fn error() {
+        #[derive(Debug)]
+        struct Outer(std::io::Error);
+
+        impl fmt::Display for Outer {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("outer")
+            }
+        }
+
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let tb = GBox::from(Outer(std::io::Error::other("inner")));
+        assert_eq!("outer", tb.to_string());
+        assert_eq!("inner", tb.source().unwrap().to_string());
+        assert_eq!(format!("{:?}", *tb), format!("{:?}", tb));
+    }
+
+    #[test]
+    fn index() {
+        let mut tb = GBox::from(vec![1, 2, 3]);
+        assert_eq!(2, tb[1]);
+        assert_eq!(&[2, 3], &tb[1..]);
+
+        tb[1] = 5;
+        assert_eq!(&[1, 5, 3], tb.as_slice());
+    }
+
+    #[test]
+    fn box_conversion() {
+        let alloc = GAlloc::default();
+
+        let tb = GBox::from_box(Box::new("foo".to_string()), alloc.clone());
+        assert_eq!("foo", tb.as_str());
+        assert_eq!(1, alloc.allocation_count());
+
+        let b = GBox::into_box(tb);
+        assert_eq!("foo", b.as_str());
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn compare_with_value() {
+        let tb = GBox::from(35);
+        assert_eq!(tb, 35);
+        assert_ne!(tb, 36);
+        assert!(tb < 36);
+        assert!(tb > 34);
+    }
+
+    #[test]
+    fn new_at_alignment() {
+        let alloc = GAlloc::default();
+
+        let tb = GBox::new_at_alignment("foo".to_string(), 256, alloc.clone());
+        assert_eq!(0, &*tb as *const String as usize % 256);
+        assert_eq!("foo", tb.as_str());
+
+        let b = GBox::into_box(tb);
+        assert_eq!("foo", b.as_str());
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_at_alignment_less_than_type() {
+        let _tb = GBox::new_at_alignment(0_u64, 1, GAlloc::default());
+    }
+
+    #[test]
+    fn unaligned() {
+        let mut buf = [0_u8; 9];
+        unsafe {
+            let ptr = buf.as_mut_ptr().add(1) as *mut u64;
+            GBox::write_unaligned(ptr, 0x0102_0304_0506_0708);
+            assert_eq!(0x0102_0304_0506_0708, GBox::read_unaligned(ptr));
+        }
+    }
+
+    #[test]
+    fn cow() {
+        let alloc = GAlloc::default();
+        let mut tb = GBox::new(vec![1, 2], alloc.clone());
+
+        GBox::cow_mut(&mut tb).push(3);
+        assert_eq!(&[1, 2, 3], tb.as_slice());
+        assert_eq!(1, alloc.allocation_count());
+
+        let other = GAlloc::default();
+        let fresh = GBox::clone_into_fresh(&tb, other.clone());
+        assert_eq!(tb, fresh);
+        assert_eq!(1, alloc.allocation_count());
+        assert_eq!(1, other.allocation_count());
+    }
+
+    #[test]
+    fn take() {
+        use std::rc::Rc;
+
+        let dropped = Rc::new(());
+        let tb = GBox::from(Some(dropped.clone()));
+        assert_eq!(2, Rc::strong_count(&dropped));
+
+        let (val, alloc) = GBox::take(tb, Some(dropped.clone()));
+        assert_eq!(2, Rc::strong_count(&dropped));
+        assert!(val.is_some());
+        assert_eq!(0, alloc.allocation_count());
+
+        let tb = GBox::new(val, alloc.clone());
+        let (val, _) = GBox::take_with_default(tb);
+        assert!(val.is_some());
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn write_value() {
+        use std::rc::Rc;
+
+        let old = Rc::new(());
+        let new = Rc::new(());
+        let alloc = GAlloc::default();
+        let mut tb = GBox::new(old.clone(), alloc.clone());
+        let ptr = tb.ptr;
+
+        GBox::write(&mut tb, new.clone());
+        assert_eq!(1, Rc::strong_count(&old));
+        assert_eq!(2, Rc::strong_count(&new));
+        assert!(Rc::ptr_eq(&new, &tb));
+        assert_eq!(ptr, tb.ptr);
+        assert_eq!(1, alloc.stats().total_alloc_count);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut tb = GBox::new(35, GAlloc::default());
+        assert_eq!(Some(&mut 35), GBox::get_mut(&mut tb));
+
+        let ptr = tb.ptr;
+        tb.ptr = core::ptr::null_mut();
+        assert_eq!(None, GBox::get_mut(&mut tb));
+        tb.ptr = ptr;
+    }
+
+    #[test]
+    fn is_dangling() {
+        let alloc = GAlloc::default();
+        let mut tb = GBox::new(35, alloc.clone());
+        assert!(!GBox::is_dangling(&tb));
+
+        let ptr = tb.ptr;
+        tb.ptr = core::ptr::null_mut();
+        assert!(GBox::is_dangling(&tb));
+
+        unsafe { alloc.dealloc(ptr as *mut u8, Layout::new::<i32>()) };
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to dereference a dangling TestBox")]
+    fn deref_dangling() {
+        let mut tb = GBox::from(35);
+        let ptr = tb.ptr;
+        tb.ptr = core::ptr::null_mut();
+
+        let _tb = unsafe { GBox::from_raw_alloc(ptr, tb.alloc.clone()) };
+        let _ = *tb;
+    }
+
+    #[test]
+    fn try_from_raw_alloc() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout) as *mut u64;
+            ptr.write(3);
+
+            let err = TestBox::<u32, GAlloc>::try_from_raw_alloc(ptr as *mut u32, &alloc);
+            assert_eq!(ptr as usize, err.unwrap_err().ptr);
+
+            let tb = TestBox::try_from_raw_alloc(ptr, &alloc).unwrap();
+            assert_eq!(3, *tb);
+        }
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn new_in_place() {
+        let alloc = GAlloc::default();
+        let tb = unsafe { GBox::<i32>::new_in_place(alloc.clone(), |ptr| ptr.write(35)) };
+        assert_eq!(35, *tb);
+        assert_eq!(1, alloc.allocation_count());
+        drop(tb);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+            GBox::<i32>::new_in_place(alloc.clone(), |_| panic!())
+        }));
+        assert!(result.is_err());
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn into_dyn() {
+        use std::fmt::Display;
+
+        let alloc = GAlloc::default();
+        let tb: TestBox<dyn Display, GAlloc> = new_dyn!(35, alloc.clone() => dyn Display);
+        assert_eq!("35", tb.to_string());
+        assert_eq!(1, alloc.allocation_count());
+
+        let leaked = GBox::leak(tb);
+        assert_eq!("35", leaked.to_string());
+        let tb = unsafe {
+            TestBox::from_raw_parts(
+                leaked as *mut dyn Display,
+                Layout::new::<i32>(),
+                alloc.clone(),
+            )
+        };
+        drop(tb);
+        assert_eq!(0, alloc.allocation_count());
+
+        let tb: TestBox<[u8], GAlloc> = into_dyn!(GBox::new([1, 2, 3], alloc.clone()) => [u8]);
+        assert_eq!(&[1, 2, 3], &*tb);
+        assert_eq!(2, tb[1]);
+    }
+
+    #[test]
+    fn eq_box() {
+        let tb = GBox::new(35, GAlloc::default());
+        assert_eq!(tb, Box::new(35));
+        assert_ne!(tb, Box::new(36));
+    }
+
+    #[test]
+    fn into_inner_or_default() {
+        let alloc = GAlloc::default();
+        let tb = GBox::new(35, alloc.clone());
+        assert_eq!(35, GBox::into_inner_or_default(tb));
+
+        let mut tb = GBox::new(35, alloc.clone());
+        let ptr = tb.ptr;
+        tb.ptr = core::ptr::null_mut();
+        assert_eq!(0, GBox::into_inner_or_default(tb));
+        unsafe { drop(GBox::from_raw_alloc(ptr, alloc.clone())) };
+
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn split_at() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(());
+        let alloc = GAlloc::default();
+        let tb = GBox::new([rc.clone(), rc.clone(), rc.clone()], alloc.clone());
+        let tb: TestBox<[Rc<()>], GAlloc> = into_dyn!(tb => [Rc<()>]);
+
+        let (left, right) = TestBox::split_at(tb, 3);
+        assert_eq!(3, left.len());
+        assert_eq!(0, right.len());
+        assert_eq!(4, Rc::strong_count(&rc));
+        // The empty slice does not allocate memory.
+        assert_eq!(1, alloc.allocation_count());
+
+        drop(right);
+        let (left, right) = TestBox::split_at(left, 1);
+        assert_eq!(1, left.len());
+        assert_eq!(2, right.len());
+        assert_eq!(4, Rc::strong_count(&rc));
+
+        drop(left);
+        drop(right);
+        assert_eq!(1, Rc::strong_count(&rc));
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn try_resize() {
+        let alloc = GAlloc::default();
+        let layout = Layout::new::<u64>();
+        let tb = TestBox::new(5_u64, alloc.clone());
+        let tb = TestBox::try_resize(tb, Layout::new::<[u64; 2]>()).unwrap();
+        let tb = TestBox::try_resize(tb, layout).unwrap();
+        assert_eq!(layout, alloc.providing_pointers()[0].1);
+        drop(tb);
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn clone_into() {
+        let alloc = GAlloc::default();
+        let other = GAlloc::default();
+
+        let src = TestBox::new(vec![1, 2, 3], alloc.clone());
+        let mut target = TestBox::new(Vec::with_capacity(8), other.clone());
+        TestBox::clone_into(&src, &mut target);
+
+        assert_eq!(vec![1, 2, 3], *target);
+        assert_eq!(1, alloc.allocation_count());
+        assert_eq!(1, other.allocation_count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn as_slice_too_long() {
+        let tb = TestBox::new([0_u16; 2], GAlloc::default());
+        let (ptr, layout, alloc) = TestBox::into_raw_parts(tb);
+        let tb = unsafe { TestBox::from_raw_parts(ptr as *mut u16, layout, alloc) };
+        assert_eq!(&[0, 0], unsafe { TestBox::as_slice(&tb, 2) });
+        unsafe { TestBox::as_slice(&tb, 3) };
+    }
+
+    #[test]
+    fn replace_alloc() {
+        let from = GAlloc::default();
+        let to = GAlloc::default();
+
+        let a = TestBox::new(vec![1, 2], from.clone());
+        let _b = TestBox::new(vec![3], from.clone());
+        assert_eq!(2, from.allocation_count());
+
+        let a = TestBox::replace_alloc(a, to.clone());
+        assert_eq!(vec![1, 2], *a);
+        assert_eq!(1, from.allocation_count());
+        assert_eq!(1, to.allocation_count());
+    }
+
+    #[test]
+    fn extend_and_collect() {
+        let mut tb: GBox<Vec<i32>> = (0..3).collect();
+        assert_eq!(vec![0, 1, 2], *tb);
+
+        tb.extend(3..5);
+        assert_eq!(vec![0, 1, 2, 3, 4], *tb);
+    }
+
+    #[test]
+    fn downcast() {
+        let alloc = GAlloc::default();
+        let tb: TestBox<dyn Any, GAlloc> =
+            into_dyn!(GBox::new(vec![1, 2], alloc.clone()) => dyn Any);
+
+        let tb = TestBox::downcast::<Vec<u8>>(tb).unwrap_err();
+        assert_eq!(1, alloc.allocation_count());
+
+        let tb = TestBox::downcast::<Vec<i32>>(tb).unwrap();
+        assert_eq!(vec![1, 2], *tb);
+        assert_eq!(1, alloc.allocation_count());
+    }
+
+    #[test]
+    fn combine() {
+        let a_alloc = GAlloc::default();
+        let b_alloc = GAlloc::default();
+
+        let a = TestBox::new(2, a_alloc.clone());
+        let b = TestBox::new(3, b_alloc.clone());
+        let product = TestBox::combine(a, b, |a, b| (a * b).to_string());
+
+        assert_eq!("6", *product);
+        assert_eq!(1, a_alloc.allocation_count());
+        assert_eq!(0, b_alloc.allocation_count());
+    }
+
+    #[test]
+    fn new_array() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let alloc = GAlloc::default();
+        let tb = TestBox::new_array(0, 5, alloc.clone());
+        assert!(tb.is_empty());
+        drop(tb);
+
+        // Panics on the 3rd clone.
+        struct Bomb(std::rc::Rc<std::cell::Cell<usize>>);
+
+        impl Clone for Bomb {
+            fn clone(&self) -> Self {
+                let n = self.0.get() + 1;
+                assert!(n < 3);
+                self.0.set(n);
+                Self(self.0.clone())
+            }
+        }
+
+        let bomb = Bomb(Default::default());
+        let count = bomb.0.clone();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            TestBox::new_array(5, bomb, alloc.clone());
+        }));
+        assert!(result.is_err());
+        assert_eq!(1, std::rc::Rc::strong_count(&count));
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn allocate_extra() {
+        let alloc = GAlloc::default();
+        let tb = TestBox::new_at_alignment(5_u8, 8, alloc.clone());
+
+        let tb = TestBox::allocate_extra(tb, 15);
+        assert_eq!(5, *tb);
+        assert_eq!(
+            vec![Layout::from_size_align(16, 8).unwrap()],
+            alloc.live_layouts()
+        );
+
+        let tb = TestBox::allocate_extra(tb, 0);
+        assert_eq!(5, *tb);
+        assert_eq!(
+            vec![Layout::new::<u8>().align_to(8).unwrap()],
+            alloc.live_layouts()
+        );
+
+        drop(tb);
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn zero_sized_type() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct Zst;
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let alloc = GAlloc::default().with_zero_size_policy(crate::ZeroSizePolicy::Violation);
+
+        drop(TestBox::new_zst(alloc.clone()));
+        drop(TestBox::new(Zst, alloc.clone()));
+        drop(TestBox::try_new([(); 3], alloc.clone()).unwrap());
+        drop(TestBox::new_at_alignment((), 16, alloc.clone()));
+        drop(TestBox::new_array(0, 1_u8, alloc.clone()));
+        let tb = TestBox::new((), alloc.clone());
+        assert_eq!(0, &*tb as *const () as usize % core::mem::align_of::<()>());
+        assert_eq!((), TestBox::into_inner(tb));
+
+        assert_eq!(1, DROPPED.load(Ordering::Relaxed));
+        assert_eq!(0, alloc.stats().total_alloc_count);
+        assert_eq!(0, alloc.stats().total_dealloc_count);
+
+        let tb = TestBox::allocate_extra(TestBox::new_zst(alloc.clone()), 8);
+        assert_eq!(
+            vec![Layout::from_size_align(8, 1).unwrap()],
+            alloc.live_layouts()
+        );
+        drop(tb);
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn zero_sized_type_from_raw() {
+        let alloc = GAlloc::default();
+        let sentinel = core::ptr::NonNull::<()>::dangling().as_ptr();
+
+        drop(unsafe { TestBox::from_raw_alloc(sentinel, alloc.clone()) });
+        drop(unsafe { TestBox::try_from_raw_alloc(sentinel, &alloc) }.unwrap());
+        assert_eq!(0, alloc.stats().total_alloc_count);
+
+        // Zero-size memory allocated via the allocator is deallocated on drop.
+        let layout = Layout::new::<()>();
+        unsafe {
+            let ptr = alloc.alloc(layout) as *mut ();
+            drop(TestBox::from_raw_alloc(ptr, alloc.clone()));
+            assert_eq!(0, alloc.allocation_count());
+
+            let ptr = alloc.alloc(layout) as *mut ();
+            drop(TestBox::try_from_raw_alloc(ptr, &alloc).unwrap());
+            assert_eq!(0, alloc.allocation_count());
+        }
+    }
+
+    #[test]
+    fn get_alloc_ref_unsized() {
+        // Does not implement `Clone` .
+        struct Backend;
+        unsafe impl GlobalAlloc for Backend {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                std::alloc::System.alloc(layout)
+            }
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                std::alloc::System.dealloc(ptr, layout)
+            }
+        }
+
+        let tb = TestBox::new([1, 2], TestAlloc::from(Backend));
+        let tb: TestBox<[i32], TestAlloc<Backend>> = into_dyn!(tb => [i32]);
+        assert_eq!(1, TestBox::get_alloc_ref(&tb).allocation_count());
+    }
 }
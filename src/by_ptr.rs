@@ -0,0 +1,200 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::TestBox;
+use core::alloc::GlobalAlloc;
+use core::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// `ByPtr` is a wrapper of [`TestBox`] , which compares and hashes by the address of the
+/// allocation instead of the value.
+///
+/// It enables to put `TestBox` into such as `HashSet` by the identity; e.g. even if `T` does not
+/// implement `Hash` , or if 2 boxes hold the same value.
+///
+/// Note that the address does not always identify the box. `TestBox` of a zero-sized type does
+/// not allocate but holds a sentinel pointer, so all such boxes of the same alignment are equal
+/// to each other. So are all the dangling boxes, whose address is 0.
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{ByPtr, GAlloc, TestBox};
+/// use std::collections::HashSet;
+///
+/// let alloc = GAlloc::default();
+/// let a = ByPtr(TestBox::new(1.0_f64, alloc.clone()));
+/// let b = ByPtr(TestBox::new(1.0_f64, alloc.clone()));
+/// assert!(a != b);
+///
+/// let mut set = HashSet::new();
+/// assert!(set.insert(a));
+/// assert!(set.insert(b));
+/// assert_eq!(2, set.len());
+/// ```
+pub struct ByPtr<T, A>(pub TestBox<T, A>)
+where
+    T: ?Sized,
+    A: GlobalAlloc;
+
+impl<T, A> From<TestBox<T, A>> for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn from(tb: TestBox<T, A>) -> Self {
+        Self(tb)
+    }
+}
+
+impl<T, A> ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    /// Returns the address of the allocation.
+    pub fn addr(&self) -> usize {
+        TestBox::addr(&self.0)
+    }
+}
+
+impl<T, A> PartialEq<Self> for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn eq(&self, rh: &Self) -> bool {
+        self.addr() == rh.addr()
+    }
+}
+
+impl<T, A> Eq for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+}
+
+impl<T, A> PartialOrd<Self> for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn partial_cmp(&self, rh: &Self) -> Option<Ordering> {
+        Some(self.cmp(rh))
+    }
+}
+
+impl<T, A> Ord for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn cmp(&self, rh: &Self) -> Ordering {
+        self.addr().cmp(&rh.addr())
+    }
+}
+
+impl<T, A> Hash for ByPtr<T, A>
+where
+    T: ?Sized,
+    A: GlobalAlloc,
+{
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.addr().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAlloc;
+
+    #[test]
+    fn order() {
+        let alloc = GAlloc::default();
+        let a = ByPtr::from(TestBox::new(2, alloc.clone()));
+        let b = ByPtr::from(TestBox::new(1, alloc.clone()));
+
+        assert_eq!(a.addr() < b.addr(), a < b);
+        assert_eq!(Ordering::Equal, a.cmp(&a));
+        assert!(a == a);
+    }
+
+    #[test]
+    fn zero_sized_type() {
+        let alloc = GAlloc::default();
+        let a = ByPtr::from(TestBox::new((), alloc.clone()));
+        let b = ByPtr::from(TestBox::new((), alloc.clone()));
+        assert!(a == b);
+    }
+}
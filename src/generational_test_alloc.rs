@@ -0,0 +1,331 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::sync::{Arc, Mutex};
+use crate::TestAlloc;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ops::Deref;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::BuildHasher;
+
+/// The default number of the deallocated pointers that [`GenerationalTestAlloc`] remembers.
+pub const DEFAULT_FREED_WINDOW: usize = 64;
+
+/// `GenerationProbe` is the result of [`GenerationalTestAlloc::probe_generation`] .
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GenerationProbe {
+    /// The pointer is being allocated with the generation.
+    Live(u64),
+    /// The pointer was deallocated recently; it was allocated with the generation.
+    RecentlyFreed(u64),
+    /// The pointer is neither being allocated nor deallocated recently.
+    Unknown,
+}
+
+#[derive(Default)]
+struct Generations {
+    next: u64,
+    live: HashMap<usize, u64>,
+    // The oldest is at the front.
+    freed: VecDeque<(usize, u64)>,
+}
+
+impl Generations {
+    fn on_alloc(&mut self, addr: usize) {
+        self.live.insert(addr, self.next);
+        self.next += 1;
+    }
+
+    fn on_dealloc(&mut self, addr: usize, window: usize) {
+        if let Some(generation) = self.live.remove(&addr) {
+            self.freed.push_back((addr, generation));
+            while window < self.freed.len() {
+                self.freed.pop_front();
+            }
+        }
+    }
+}
+
+/// `GenerationalTestAlloc` is an implementation for `GlobalAlloc` , which assigns an
+/// incrementing generation number to each allocation on top of [`TestAlloc`] .
+///
+/// It remembers the generations of the recently deallocated pointers as well, so that container
+/// code can detect use-after-free in tests via [`probe_generation`] . The generation tells a
+/// pointer allocated again at the same address apart from the freed one.
+///
+/// It dereferences to the wrapped `TestAlloc` . The cloned instances share the generations.
+/// See also [`TestAlloc::with_generation_counter`] .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{GAlloc, GenerationProbe};
+/// use std::alloc::{GlobalAlloc, Layout};
+///
+/// let alloc = GAlloc::default().with_generation_counter();
+/// let layout = Layout::new::<u64>();
+///
+/// unsafe {
+///     let ptr = alloc.alloc(layout);
+///     assert_eq!(GenerationProbe::Live(0), alloc.probe_generation(ptr));
+///
+///     alloc.dealloc(ptr, layout);
+///     assert_eq!(GenerationProbe::RecentlyFreed(0), alloc.probe_generation(ptr));
+/// }
+/// ```
+///
+/// [`probe_generation`]: Self::probe_generation
+pub struct GenerationalTestAlloc<A, S = RandomState>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    alloc: TestAlloc<A, S>,
+    generations: Arc<Mutex<Generations>>,
+    freed_window: usize,
+}
+
+impl<A, S> From<TestAlloc<A, S>> for GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    fn from(alloc: TestAlloc<A, S>) -> Self {
+        Self {
+            alloc,
+            generations: Arc::default(),
+            freed_window: DEFAULT_FREED_WINDOW,
+        }
+    }
+}
+
+impl<A, S> Clone for GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc + Clone,
+    S: BuildHasher,
+{
+    fn clone(&self) -> Self {
+        Self {
+            alloc: self.alloc.clone(),
+            generations: self.generations.clone(),
+            freed_window: self.freed_window,
+        }
+    }
+}
+
+impl<A, S> GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    /// Sets the number of the deallocated pointers to remember, and returns `self` .
+    ///
+    /// The oldest one is forgotten first. The default is [`DEFAULT_FREED_WINDOW`] .
+    pub fn with_freed_window(mut self, window: usize) -> Self {
+        self.freed_window = window;
+        self
+    }
+
+    /// Returns the generation of `ptr` if it is being allocated or if it was deallocated
+    /// recently, or returns `GenerationProbe::Unknown` .
+    ///
+    /// If `ptr` is deallocated more than once recently, the latest generation is returned.
+    pub fn probe_generation(&self, ptr: *mut u8) -> GenerationProbe {
+        let addr = ptr as usize;
+        let generations = self.generations.lock().unwrap();
+
+        if let Some(&generation) = generations.live.get(&addr) {
+            return GenerationProbe::Live(generation);
+        }
+        generations
+            .freed
+            .iter()
+            .rev()
+            .find(|&&(a, _)| a == addr)
+            .map_or(GenerationProbe::Unknown, |&(_, generation)| {
+                GenerationProbe::RecentlyFreed(generation)
+            })
+    }
+}
+
+impl<A, S> Deref for GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    type Target = TestAlloc<A, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.alloc
+    }
+}
+
+unsafe impl<A, S> GlobalAlloc for GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc.alloc(layout);
+        if !ptr.is_null() {
+            self.generations.lock().unwrap().on_alloc(ptr as usize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.generations.lock().unwrap().on_alloc(ptr as usize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Forget the generation before the backend allocator frees `ptr` ; another thread can be
+        // given the same address at once.
+        self.generations
+            .lock()
+            .unwrap()
+            .on_dealloc(ptr as usize, self.freed_window);
+        self.alloc.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.alloc.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            let mut generations = self.generations.lock().unwrap();
+            generations.on_dealloc(ptr as usize, self.freed_window);
+            generations.on_alloc(new_ptr as usize);
+        }
+        new_ptr
+    }
+}
+
+impl<A, S> fmt::Debug for GenerationalTestAlloc<A, S>
+where
+    A: GlobalAlloc + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GenerationalTestAlloc")
+            .field("alloc", &self.alloc)
+            .field("freed_window", &self.freed_window)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAlloc;
+
+    #[test]
+    fn probe_generation() {
+        let alloc = GAlloc::default().with_generation_counter();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let a = alloc.alloc(layout);
+            let b = alloc.alloc_zeroed(layout);
+            assert_eq!(GenerationProbe::Live(0), alloc.probe_generation(a));
+            assert_eq!(GenerationProbe::Live(1), alloc.probe_generation(b));
+
+            let c = alloc.realloc(b, layout, 16);
+            assert_eq!(GenerationProbe::Live(2), alloc.probe_generation(c));
+            if c != b {
+                assert_eq!(GenerationProbe::RecentlyFreed(1), alloc.probe_generation(b));
+            }
+
+            alloc.dealloc(a, layout);
+            assert_eq!(GenerationProbe::RecentlyFreed(0), alloc.probe_generation(a));
+            alloc.dealloc(c, Layout::from_size_align(16, layout.align()).unwrap());
+        }
+    }
+
+    #[test]
+    fn freed_window() {
+        let alloc = GAlloc::default()
+            .with_generation_counter()
+            .with_freed_window(1);
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let a = alloc.alloc(layout);
+            let b = alloc.alloc(layout);
+            alloc.dealloc(a, layout);
+            alloc.dealloc(b, layout);
+
+            assert_eq!(GenerationProbe::Unknown, alloc.probe_generation(a));
+            assert_eq!(GenerationProbe::RecentlyFreed(1), alloc.probe_generation(b));
+        }
+    }
+}
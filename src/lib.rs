@@ -68,6 +68,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 #![deny(missing_docs)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
 //! `Gharial` is a test tool for program to manipulate memory directly.
 //!
@@ -80,9 +81,69 @@
 //! some trouble like memory leak could be occurred.
 //!
 //! `Gharial` helps to test such program.
+//!
+//! # Features
+//!
+//! - `allocator_api` : Implements the nightly `core::alloc::Allocator` for [`TestAlloc`] .
+//!   (Requires nightly compiler.)
+//! - `backtraces` : Enables `TestAlloc::with_backtraces` to print where the leaked memory is
+//!   allocated.
+//! - `loom` : Replaces the synchronization primitives of [`TestAlloc`] and the other
+//!   allocators with those of `loom` if the crate is built with `RUSTFLAGS="--cfg loom"` , so
+//!   that they can be used in `loom::model` . (Then they can not be used out of `loom::model` .)
+//!   The feature alone changes nothing.
+//! - `proptest` : Enables `alloc_scenarios` , a `proptest` strategy to generate random
+//!   sequences of allocations and deallocations.
+//! - `quickcheck` : Implements `quickcheck::Arbitrary` for [`AllocStats`] .
+//! - `serde` : Enables `TestAlloc::export_json` to report the live allocations in JSON, and
+//!   `AllocationSnapshot` to load the report.
+//! - `strict_provenance` : Makes [`TestAlloc`] track the addresses instead of the raw pointers,
+//!   so that the tracking information is valid under Miri's provenance checks.
+//! - `verify` : Makes `TestAlloc` panic if the backend `GlobalAlloc::alloc_zeroed` returns
+//!   memory which is not zeroed.
+
+#[macro_use]
+mod macros;
 
+mod alignment_violation;
 mod alloc;
 mod boxed;
+mod by_ptr;
+mod generational_test_alloc;
+mod history;
+mod invalid_ptr_error;
+#[cfg(feature = "proptest")]
+mod prop;
+mod round_robin_alloc;
+mod serial_alloc;
+mod sharded_alloc;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod stats;
+mod subregion_test_alloc;
+mod sync;
+mod test_alloc_builder;
+mod violation;
+mod zeroed_contract_alloc;
 
-pub use alloc::{GAlloc, MaybeAlloc, NeverAlloc, TestAlloc};
+pub use alignment_violation::AlignmentViolation;
+pub use alloc::{
+    FastTestAlloc, GAlloc, MaybeAlloc, NeverAlloc, TestAlloc, DEFAULT_HISTORY_CAPACITY,
+};
 pub use boxed::{GBox, TestBox};
+pub use by_ptr::ByPtr;
+pub use generational_test_alloc::{GenerationProbe, GenerationalTestAlloc, DEFAULT_FREED_WINDOW};
+pub use history::{AllocEvent, AllocEventKind, AllocHistory};
+pub use invalid_ptr_error::InvalidPtrError;
+#[cfg(feature = "proptest")]
+pub use prop::{alloc_scenarios, AllocScenario};
+pub use round_robin_alloc::RoundRobinAlloc;
+pub use serial_alloc::SerialAlloc;
+pub use sharded_alloc::ShardedTestAlloc;
+#[cfg(feature = "serde")]
+pub use snapshot::{AllocationRecord, AllocationSnapshot};
+pub use stats::AllocStats;
+pub use subregion_test_alloc::SubregionTestAlloc;
+pub use test_alloc_builder::TestAllocBuilder;
+pub use violation::{DropPolicy, Violation, ViolationPolicy, ZeroSizePolicy};
+pub use zeroed_contract_alloc::ZeroedContractAlloc;
@@ -0,0 +1,217 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+/// Asserts that the number of the live allocations of [`TestAlloc`] equals to `expected` .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{assert_alloc_count, GAlloc, GBox};
+///
+/// let alloc = GAlloc::default();
+/// assert_alloc_count!(alloc, 0);
+///
+/// let _five = GBox::new(5, alloc.clone());
+/// assert_alloc_count!(alloc, 1);
+/// ```
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[macro_export]
+macro_rules! assert_alloc_count {
+    ($alloc:expr, $expected:expr $(,)?) => {{
+        let actual = $alloc.allocation_count();
+        let expected: usize = $expected;
+        assert_eq!(
+            actual, expected,
+            "expected {} live allocations, got {}",
+            expected, actual
+        );
+    }};
+}
+
+/// Asserts that the total bytes of the live allocations of [`TestAlloc`] equals to `expected` .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{assert_alloc_bytes, GAlloc, GBox};
+///
+/// let alloc = GAlloc::default();
+/// assert_alloc_bytes!(alloc, 0);
+///
+/// let _five = GBox::new(5_u64, alloc.clone());
+/// assert_alloc_bytes!(alloc, 8);
+/// ```
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[macro_export]
+macro_rules! assert_alloc_bytes {
+    ($alloc:expr, $expected:expr $(,)?) => {{
+        let actual = $alloc.allocation_bytes();
+        let expected: usize = $expected;
+        assert_eq!(
+            actual, expected,
+            "expected {} live allocated bytes, got {}",
+            expected, actual
+        );
+    }};
+}
+
+/// Asserts that the live allocations of [`TestAlloc`] are exactly `[(size, align), ...]` .
+///
+/// The order does not matter; the both sides are compared as multisets. It is more precise than
+/// [`assert_alloc_count`] .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{assert_exact_allocations, GAlloc, GBox};
+///
+/// let alloc = GAlloc::default();
+/// assert_exact_allocations!(alloc, []);
+///
+/// let _a = GBox::new(5_u64, alloc.clone());
+/// let _b = GBox::new(5_u16, alloc.clone());
+/// let _c = GBox::new(5_u64, alloc.clone());
+/// assert_exact_allocations!(alloc, [(2, 2), (8, 8), (8, 8)]);
+/// ```
+///
+/// [`TestAlloc`]: crate::TestAlloc
+/// [`assert_alloc_count`]: crate::assert_alloc_count
+#[macro_export]
+macro_rules! assert_exact_allocations {
+    ($alloc:expr, [$(($size:expr, $align:expr)),* $(,)?] $(,)?) => {{
+        let mut expected: Vec<(usize, usize)> = vec![$(($size, $align)),*];
+        expected.sort_unstable();
+
+        let mut actual: Vec<(usize, usize)> = $alloc
+            .providing_pointers()
+            .iter()
+            .map(|(_, layout)| (layout.size(), layout.align()))
+            .collect();
+        actual.sort_unstable();
+
+        if expected != actual {
+            panic!(
+                "The live allocations do not match.\nexpected (size, align): {:?}\nactual (size, align): {:?}",
+                expected, actual
+            );
+        }
+    }};
+}
+
+/// Converts `TestBox<T, A>` into `TestBox<U, A>` by the unsized coercion from `T` to `U` ; e.g.
+/// `TestBox<i32, A>` into `TestBox<dyn Display, A>` , or `TestBox<[u8; 3], A>` into
+/// `TestBox<[u8], A>` .
+///
+/// The memory is not re-allocated. It fails to compile unless `*mut T` is coerced to `*mut U` .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{into_dyn, GAlloc, GBox, TestBox};
+/// use std::error::Error;
+/// use std::io;
+///
+/// let err = GBox::new(io::Error::from(io::ErrorKind::Other), GAlloc::default());
+/// let err: TestBox<dyn Error, GAlloc> = into_dyn!(err => dyn Error);
+/// assert_eq!("other error", err.to_string());
+/// ```
+#[macro_export]
+macro_rules! into_dyn {
+    ($tb:expr => $t:ty) => {{
+        let (ptr, layout, alloc) = $crate::TestBox::into_raw_parts($tb);
+        let ptr: *mut $t = ptr;
+        unsafe { $crate::TestBox::from_raw_parts(ptr, layout, alloc) }
+    }};
+}
+
+/// Creates `TestBox<U, A>` from a value of `T` and an allocator, where `T` is coerced to
+/// unsized type `U` ; e.g. `new_dyn!(5, alloc => dyn Display)` .
+///
+/// It is the same as `into_dyn!(TestBox::new(val, alloc) => U)` . See also [`into_dyn`] .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{new_dyn, GAlloc, TestBox};
+/// use std::fmt::Debug;
+///
+/// let alloc = GAlloc::default();
+/// let tb: TestBox<dyn Debug, GAlloc> = new_dyn!("foo", alloc.clone() => dyn Debug);
+/// assert_eq!("\"foo\"", format!("{:?}", tb));
+/// ```
+///
+/// [`into_dyn`]: crate::into_dyn
+#[macro_export]
+macro_rules! new_dyn {
+    ($val:expr, $alloc:expr => $t:ty) => {
+        $crate::into_dyn!($crate::TestBox::new($val, $alloc) => $t)
+    };
+}
@@ -0,0 +1,192 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::TestAlloc;
+use core::alloc::{GlobalAlloc, Layout};
+use proptest::prelude::*;
+use std::hash::BuildHasher;
+
+/// `AllocScenario` is a random sequence of the operations on [`TestAlloc`] generated by
+/// [`alloc_scenarios`] .
+///
+/// [`TestAlloc`]: crate::TestAlloc
+/// [`alloc_scenarios`]: crate::alloc_scenarios
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocScenario {
+    /// The layouts to be allocated in this order.
+    pub layouts: Vec<Layout>,
+    /// The indices of `layouts` to be deallocated in this order. It is a permutation of
+    /// `0..layouts.len()` .
+    pub dealloc_order: Vec<usize>,
+}
+
+impl AllocScenario {
+    /// Allocates all the layouts via `alloc` , and then deallocates them in `dealloc_order` .
+    ///
+    /// Panics if the statistics of `alloc` are inconsistent with the operations.
+    /// `alloc` must not provide any pointer before calling this method.
+    pub fn run<A, S>(&self, alloc: &TestAlloc<A, S>)
+    where
+        A: GlobalAlloc,
+        S: BuildHasher,
+    {
+        assert_eq!(0, alloc.allocation_count());
+        let before = alloc.stats();
+
+        let ptrs: Vec<*mut u8> = self
+            .layouts
+            .iter()
+            .map(|&layout| unsafe { alloc.alloc(layout) })
+            .collect();
+        let live: Vec<(*mut u8, Layout)> = ptrs
+            .iter()
+            .zip(&self.layouts)
+            .filter(|(ptr, _)| !ptr.is_null())
+            .map(|(&ptr, &layout)| (ptr, layout))
+            .collect();
+
+        assert_eq!(live.len(), alloc.allocation_count());
+        let bytes: usize = live.iter().map(|(_, layout)| layout.size()).sum();
+        assert_eq!(bytes, alloc.allocation_bytes());
+        for &(ptr, layout) in &live {
+            assert_eq!(0, ptr as usize % layout.align());
+        }
+
+        for &i in &self.dealloc_order {
+            if !ptrs[i].is_null() {
+                unsafe { alloc.dealloc(ptrs[i], self.layouts[i]) };
+            }
+        }
+
+        let after = alloc.stats();
+        assert_eq!(0, alloc.allocation_count());
+        assert_eq!(0, after.live_bytes);
+        assert_eq!(
+            before.total_alloc_count + live.len(),
+            after.total_alloc_count
+        );
+        assert_eq!(
+            before.total_dealloc_count + live.len(),
+            after.total_dealloc_count
+        );
+    }
+}
+
+/// Returns a `proptest` strategy to generate [`AllocScenario`] .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{alloc_scenarios, GAlloc};
+/// use proptest::test_runner::TestRunner;
+///
+/// TestRunner::default()
+///     .run(&alloc_scenarios(), |scenario| {
+///         scenario.run(&GAlloc::default());
+///         Ok(())
+///     })
+///     .unwrap();
+/// ```
+pub fn alloc_scenarios() -> impl Strategy<Value = AllocScenario> {
+    let layout = (1_usize..=256, 0_u32..=6)
+        .prop_map(|(size, align)| Layout::from_size_align(size, 1 << align).unwrap());
+
+    prop::collection::vec(layout, 0..32).prop_flat_map(|layouts| {
+        let order: Vec<usize> = (0..layouts.len()).collect();
+        (Just(layouts), Just(order).prop_shuffle()).prop_map(|(layouts, dealloc_order)| {
+            AllocScenario {
+                layouts,
+                dealloc_order,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GAlloc, MaybeAlloc, NeverAlloc};
+
+    proptest! {
+        #[test]
+        fn test_alloc(scenario in alloc_scenarios()) {
+            scenario.run(&GAlloc::default());
+        }
+
+        #[test]
+        fn never_alloc(scenario in alloc_scenarios()) {
+            scenario.run(&TestAlloc::<NeverAlloc>::default());
+        }
+
+        #[test]
+        fn maybe_alloc(scenario in alloc_scenarios()) {
+            scenario.run(&TestAlloc::<MaybeAlloc>::default());
+        }
+    }
+}
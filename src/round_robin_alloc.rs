@@ -0,0 +1,242 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::sync::AtomicBool;
+use crate::sync::{Arc, Mutex};
+use core::alloc::{GlobalAlloc, Layout};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+/// `RoundRobinAlloc` is an implementation for `GlobalAlloc` , which delegates the allocation
+/// requests to 2 backend allocators alternately.
+///
+/// The odd-numbered allocations go to `A` , and the even-numbered allocations go to `B` .
+/// `RoundRobinAlloc` remembers which allocator each pointer belongs to, and `dealloc` passes the
+/// pointer to the allocator that allocated it.
+///
+/// It helps to test that container code doesn't assume all allocations come from the same
+/// backend.
+pub struct RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc,
+    B: GlobalAlloc,
+{
+    a: A,
+    b: B,
+    next_is_b: Arc<AtomicBool>,
+    // The value is true if the pointer is allocated by `b` .
+    owners: Arc<Mutex<HashMap<*mut u8, bool>>>,
+}
+
+impl<A, B> Default for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc + Default,
+    B: GlobalAlloc + Default,
+{
+    fn default() -> Self {
+        Self::new(A::default(), B::default())
+    }
+}
+
+impl<A, B> Clone for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc + Clone,
+    B: GlobalAlloc + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            a: self.a.clone(),
+            b: self.b.clone(),
+            next_is_b: self.next_is_b.clone(),
+            owners: self.owners.clone(),
+        }
+    }
+}
+
+impl<A, B> RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc,
+    B: GlobalAlloc,
+{
+    /// Creates a new instance.
+    // The keys of the map are never dereferenced. See the comment on the `Send` implementation.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a,
+            b,
+            next_is_b: Arc::default(),
+            owners: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+unsafe impl<A, B> GlobalAlloc for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc,
+    B: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let is_b = self.next_is_b.fetch_xor(true, Ordering::Relaxed);
+        let ptr = if is_b {
+            self.b.alloc(layout)
+        } else {
+            self.a.alloc(layout)
+        };
+
+        if !ptr.is_null() {
+            self.owners.lock().unwrap().insert(ptr, is_b);
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let is_b = self.owners.lock().unwrap().remove(&ptr);
+        match is_b {
+            None => panic!("RoundRobinAlloc.dealloc() is passed a pointer which is not allocated."),
+            Some(true) => self.b.dealloc(ptr, layout),
+            Some(false) => self.a.dealloc(ptr, layout),
+        }
+    }
+}
+
+impl<A, B> fmt::Debug for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc + fmt::Debug,
+    B: GlobalAlloc + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoundRobinAlloc")
+            .field("a", &self.a)
+            .field("b", &self.b)
+            .field("info", &Arc::as_ptr(&self.owners))
+            .finish()
+    }
+}
+
+// `Send` is not implemented automatically because the key type of the `owners` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Send` manually.
+unsafe impl<A, B> Send for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc + Send,
+    B: GlobalAlloc + Send,
+{
+}
+
+// `Sync` is not implemented automatically because the key type of the `owners` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Sync` manually.
+unsafe impl<A, B> Sync for RoundRobinAlloc<A, B>
+where
+    A: GlobalAlloc + Send + Sync,
+    B: GlobalAlloc + Send + Sync,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAlloc;
+
+    #[test]
+    fn alternate() {
+        let a = GAlloc::default();
+        let b = GAlloc::default();
+        let alloc = RoundRobinAlloc::new(a.clone(), b.clone());
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptrs: Vec<*mut u8> = (0..5).map(|_| alloc.alloc(layout)).collect();
+            assert_eq!(3, a.allocation_count());
+            assert_eq!(2, b.allocation_count());
+
+            assert!(a.providing_pointers().contains(&(ptrs[0], layout)));
+            assert!(b.providing_pointers().contains(&(ptrs[1], layout)));
+
+            for ptr in ptrs.into_iter().rev() {
+                alloc.dealloc(ptr, layout);
+            }
+        }
+
+        assert_eq!(0, a.allocation_count());
+        assert_eq!(0, b.allocation_count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn dealloc_unknown_pointer() {
+        let alloc = RoundRobinAlloc::<GAlloc, GAlloc>::default();
+        let layout = Layout::new::<u64>();
+        let mut x = 0_u64;
+        unsafe { alloc.dealloc(&mut x as *mut u64 as *mut u8, layout) };
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::sync::AtomicU64;
+use crate::sync::{Arc, Mutex};
+use core::alloc::{GlobalAlloc, Layout};
+use std::alloc::System;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+/// `SerialAlloc` is an implementation for `GlobalAlloc` , which serializes all the requests.
+///
+/// It is a wrapper of another `GlobalAlloc` , and holds a lock while delegating each request to
+/// the backend allocator. Cloned instances share the lock, so that at most one request is being
+/// processed among them at the same time.
+///
+/// It helps to reproduce data races in allocator-adjacent code by forcing a specific interleaving.
+#[derive(Debug)]
+pub struct SerialAlloc<A = System>
+where
+    A: GlobalAlloc,
+{
+    alloc: A,
+    lock: Arc<Mutex<()>>,
+    lock_duration_ns: Arc<AtomicU64>,
+}
+
+impl<A> Default for SerialAlloc<A>
+where
+    A: GlobalAlloc + Default,
+{
+    fn default() -> Self {
+        Self::from(A::default())
+    }
+}
+
+impl<A> From<A> for SerialAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    fn from(alloc: A) -> Self {
+        Self {
+            alloc,
+            lock: Arc::default(),
+            lock_duration_ns: Arc::default(),
+        }
+    }
+}
+
+impl<A> Clone for SerialAlloc<A>
+where
+    A: GlobalAlloc + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            alloc: self.alloc.clone(),
+            lock: self.lock.clone(),
+            lock_duration_ns: self.lock_duration_ns.clone(),
+        }
+    }
+}
+
+impl<A> SerialAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    /// Returns the total time in nanoseconds that the lock has been held.
+    pub fn lock_duration_ns(&self) -> u64 {
+        self.lock_duration_ns.load(Ordering::Relaxed)
+    }
+
+    fn serialize<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        // The lock guards nothing; ignore the poison.
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let start = Instant::now();
+        let ret = f();
+        let elapsed = start.elapsed().as_nanos() as u64;
+        self.lock_duration_ns.fetch_add(elapsed, Ordering::Relaxed);
+
+        ret
+    }
+}
+
+unsafe impl<A> GlobalAlloc for SerialAlloc<A>
+where
+    A: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.serialize(|| self.alloc.alloc(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.serialize(|| self.alloc.dealloc(ptr, layout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAlloc;
+
+    #[test]
+    fn alloc_dealloc() {
+        let alloc = SerialAlloc::<GAlloc>::default();
+        let cloned = alloc.clone();
+        assert_eq!(0, alloc.lock_duration_ns());
+
+        let layout = Layout::new::<u64>();
+        unsafe {
+            let ptr = alloc.alloc(layout);
+            assert!(!ptr.is_null());
+            cloned.dealloc(ptr, layout);
+        }
+
+        assert_eq!(alloc.lock_duration_ns(), cloned.lock_duration_ns());
+    }
+
+    #[test]
+    fn multi_threads() {
+        let alloc = SerialAlloc::<GAlloc>::default();
+        let layout = Layout::new::<u64>();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let alloc = alloc.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        unsafe {
+                            let ptr = alloc.alloc(layout);
+                            alloc.dealloc(ptr, layout);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::sync::{Arc, Mutex};
+use crate::Violation;
+use core::alloc::{GlobalAlloc, Layout};
+use std::collections::HashMap;
+use std::fmt;
+
+/// `ShardedTestAlloc` is a variant of [`TestAlloc`] for multi-threaded tests.
+///
+/// It checks the same things as `TestAlloc` does, however, it partitions the tracking
+/// information into `N` independently locked shards by the pointer address. It reduces the lock
+/// contention while many threads allocate or deallocate at the same time.
+///
+/// Like `TestAlloc` , the cloned instances share the tracking information, and the leak check is
+/// done when the last cloned instance is dropped.
+///
+/// # Panics
+///
+/// The constructors panic if `N` is 0.
+///
+/// [`TestAlloc`]: crate::TestAlloc
+pub struct ShardedTestAlloc<A, const N: usize>
+where
+    A: GlobalAlloc,
+{
+    alloc: A,
+    shards: Arc<[Mutex<HashMap<*mut u8, Layout>>; N]>,
+}
+
+impl<A, const N: usize> Default for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc + Default,
+{
+    fn default() -> Self {
+        Self::from(A::default())
+    }
+}
+
+impl<A, const N: usize> From<A> for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc,
+{
+    // The keys of the map are never dereferenced. See the comment on the `Send` implementation.
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn from(alloc: A) -> Self {
+        assert!(0 < N, "ShardedTestAlloc requires at least 1 shard.");
+        Self {
+            alloc,
+            shards: Arc::new(core::array::from_fn(|_| Mutex::default())),
+        }
+    }
+}
+
+impl<A, const N: usize> Clone for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            alloc: self.alloc.clone(),
+            shards: self.shards.clone(),
+        }
+    }
+}
+
+impl<A, const N: usize> Drop for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc,
+{
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.shards) != 1 {
+            return;
+        }
+
+        // Panicking here during unwinding would abort the process and hide the original panic
+        // message.
+        if std::thread::panicking() {
+            for (ptr, layout) in self.providing_pointers() {
+                eprintln!("{}", Violation::Leak { ptr, layout });
+            }
+        } else if let Some(&(ptr, layout)) = self.providing_pointers().first() {
+            panic!("{}", Violation::Leak { ptr, layout });
+        }
+    }
+}
+
+unsafe impl<A, const N: usize> GlobalAlloc for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc.alloc(layout);
+        if !ptr.is_null() {
+            let prev = self.shard(ptr).lock().unwrap().insert(ptr, layout);
+            if prev.is_some() {
+                panic!("{}", Violation::DuplicatedAlloc { ptr, layout });
+            }
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // `GlobalAlloc::dealloc` interface does not define the behavior when ptr is null.
+        if ptr.is_null() {
+            panic!("{}", Violation::NullDealloc { layout });
+        }
+
+        let prev = self.shard(ptr).lock().unwrap().remove(&ptr);
+        match prev {
+            None => panic!("{}", Violation::UnknownDealloc { ptr, layout }),
+            Some(alloc_layout) => {
+                if layout != alloc_layout {
+                    let violation = Violation::LayoutMismatch {
+                        ptr,
+                        alloc_layout,
+                        dealloc_layout: layout,
+                    };
+                    panic!("{}", violation);
+                }
+            }
+        }
+
+        self.alloc.dealloc(ptr, layout);
+    }
+}
+
+impl<A, const N: usize> ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc,
+{
+    /// Returns the list of pointers and layouts that were allocated and not deallocated.
+    /// The returned value is sorted by the pointer.
+    pub fn providing_pointers(&self) -> Vec<(*mut u8, Layout)> {
+        let mut ret = Vec::new();
+        for shard in self.shards.iter() {
+            let shard = shard.lock().unwrap();
+            ret.extend(shard.iter().map(|(&ptr, &layout)| (ptr, layout)));
+        }
+        ret.sort_unstable_by_key(|&(ptr, _)| ptr);
+        ret
+    }
+
+    /// Returns the number of pointers that were allocated and not deallocated.
+    pub fn allocation_count(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().unwrap().len()).sum()
+    }
+
+    fn shard(&self, ptr: *mut u8) -> &Mutex<HashMap<*mut u8, Layout>> {
+        &self.shards[(ptr as usize >> 3) % N]
+    }
+}
+
+impl<A, const N: usize> fmt::Debug for ShardedTestAlloc<A, N>
+where
+    A: GlobalAlloc + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedTestAlloc")
+            .field("alloc", &self.alloc)
+            .field("info", &Arc::as_ptr(&self.shards))
+            .finish()
+    }
+}
+
+// `Send` is not implemented automatically because the key type of the `shards` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Send` manually.
+unsafe impl<A, const N: usize> Send for ShardedTestAlloc<A, N> where A: GlobalAlloc + Send {}
+
+// `Sync` is not implemented automatically because the key type of the `shards` (*mut u8)
+// does not implement `Send` . However, it is used as an integer and never to be dereferenced.
+// It is safe to implement `Sync` manually.
+unsafe impl<A, const N: usize> Sync for ShardedTestAlloc<A, N> where A: GlobalAlloc + Send + Sync {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    type Alloc = ShardedTestAlloc<System, 4>;
+
+    #[test]
+    fn multi_threads() {
+        let alloc = Alloc::default();
+        let layout = Layout::new::<u64>();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let alloc = alloc.clone();
+                std::thread::spawn(move || {
+                    let ptrs: Vec<usize> = (0..100)
+                        .map(|_| unsafe { alloc.alloc(layout) } as usize)
+                        .collect();
+                    for ptr in ptrs {
+                        unsafe { alloc.dealloc(ptr as *mut u8, layout) };
+                    }
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(0, alloc.allocation_count());
+    }
+
+    #[test]
+    fn providing_pointers() {
+        let alloc = Alloc::default();
+        let layout = Layout::new::<u64>();
+
+        let mut ptrs: Vec<*mut u8> = (0..16).map(|_| unsafe { alloc.alloc(layout) }).collect();
+        ptrs.sort();
+        let expected: Vec<(*mut u8, Layout)> = ptrs.iter().map(|&p| (p, layout)).collect();
+        assert_eq!(expected, alloc.providing_pointers());
+
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn layout_mismatch() {
+        let alloc = Alloc::default();
+        unsafe {
+            let ptr = alloc.alloc(Layout::new::<u64>());
+            alloc.dealloc(ptr, Layout::new::<u32>());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn leak() {
+        let alloc = Alloc::default();
+        unsafe { alloc.alloc(Layout::new::<u64>()) };
+    }
+
+    #[test]
+    fn drop_while_panicking() {
+        let result = std::thread::spawn(|| {
+            let alloc = Alloc::default();
+            let _ptr = unsafe { alloc.alloc(Layout::new::<u8>()) };
+            panic!("original panic");
+        })
+        .join();
+
+        let payload = result.unwrap_err();
+        assert_eq!(Some(&"original panic"), payload.downcast_ref::<&str>());
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// `AllocationSnapshot` is the machine-readable list of the live allocations of [`TestAlloc`] .
+///
+/// It is serialized as a JSON array of `{"ptr": "0x...", "size": N, "align": M}` . See
+/// [`TestAlloc::export_json`] and [`import_json`] .
+///
+/// [`TestAlloc`]: crate::TestAlloc
+/// [`TestAlloc::export_json`]: crate::TestAlloc::export_json
+/// [`import_json`]: Self::import_json
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AllocationSnapshot {
+    /// The allocations sorted by the address.
+    pub allocations: Vec<AllocationRecord>,
+}
+
+/// `AllocationRecord` is an allocation in [`AllocationSnapshot`] .
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AllocationRecord {
+    /// The address of the pointer.
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    pub ptr: usize,
+    /// The size of the layout.
+    pub size: usize,
+    /// The alignment of the layout.
+    pub align: usize,
+}
+
+impl AllocationSnapshot {
+    /// Parses `json` that [`TestAlloc::export_json`] returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gharial::{AllocationSnapshot, GAlloc, TestBox};
+    ///
+    /// let alloc = GAlloc::default();
+    /// let _five = TestBox::new(5_u32, alloc.clone());
+    ///
+    /// let snapshot = AllocationSnapshot::import_json(&alloc.export_json()).unwrap();
+    /// assert_eq!(1, snapshot.allocations.len());
+    /// assert_eq!(4, snapshot.allocations[0].size);
+    /// ```
+    ///
+    /// [`TestAlloc::export_json`]: crate::TestAlloc::export_json
+    pub fn import_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+fn serialize_hex<S>(ptr: &usize, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("{:#x}", ptr))
+}
+
+fn deserialize_hex<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| serde::de::Error::custom(format!("{} does not start with 0x", s)))?;
+    usize::from_str_radix(digits, 16).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_format() {
+        let snapshot = AllocationSnapshot {
+            allocations: vec![AllocationRecord {
+                ptr: 0x1000,
+                size: 32,
+                align: 8,
+            }],
+        };
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(r#"[{"ptr":"0x1000","size":32,"align":8}]"#, json);
+        assert_eq!(snapshot, AllocationSnapshot::import_json(&json).unwrap());
+
+        assert!(AllocationSnapshot::import_json(r#"[{"ptr":"1000","size":1,"align":1}]"#).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::TestAlloc;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ops::Deref;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+
+/// `SubregionTestAlloc` is an implementation for `GlobalAlloc` , which tracks the allocations
+/// of a phase of a test apart from the parent [`TestAlloc`] .
+///
+/// It has its own tracking information, and delegates the requests to the parent. So every
+/// pointer is tracked by both of them. On the drop, it checks the leaks only in its own tracking
+/// information; the parent keeps tracking the allocations across the phases.
+///
+/// It dereferences to the `TestAlloc` of the subregion. See also [`TestAlloc::subregion`] .
+///
+/// # Examples
+///
+/// ```
+/// use gharial::{GAlloc, TestBox};
+///
+/// let parent = GAlloc::default();
+/// let survivor = {
+///     let phase = parent.clone().subregion();
+///     let tmp = TestBox::new(1, &*phase);
+///     assert_eq!(1, phase.allocation_count());
+///     assert_eq!(1, parent.allocation_count());
+///     drop(tmp);
+///
+///     TestBox::new(2, parent.clone())
+/// };
+/// assert_eq!(1, parent.allocation_count());
+/// ```
+pub struct SubregionTestAlloc<A, S = RandomState>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    region: TestAlloc<TestAlloc<A, S>>,
+}
+
+impl<A, S> From<TestAlloc<A, S>> for SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    fn from(parent: TestAlloc<A, S>) -> Self {
+        Self {
+            region: TestAlloc::from(parent),
+        }
+    }
+}
+
+impl<A, S> Clone for SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc + Clone,
+    S: BuildHasher,
+{
+    fn clone(&self) -> Self {
+        Self {
+            region: self.region.clone(),
+        }
+    }
+}
+
+impl<A, S> SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    /// Provides a reference to the parent `TestAlloc` .
+    pub fn parent(&self) -> &TestAlloc<A, S> {
+        self.region.inner()
+    }
+}
+
+impl<A, S> Deref for SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    type Target = TestAlloc<TestAlloc<A, S>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.region
+    }
+}
+
+unsafe impl<A, S> GlobalAlloc for SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc,
+    S: BuildHasher,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.region.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.region.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.region.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.region.realloc(ptr, layout, new_size)
+    }
+}
+
+impl<A, S> fmt::Debug for SubregionTestAlloc<A, S>
+where
+    A: GlobalAlloc + fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubregionTestAlloc")
+            .field("region", &self.region)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DropPolicy, GAlloc};
+
+    #[test]
+    fn tracks_both() {
+        let parent = GAlloc::default();
+        let region = parent.clone().subregion();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = region.alloc(layout);
+            assert_eq!(vec![(ptr, layout)], region.providing_pointers());
+            assert_eq!(vec![(ptr, layout)], parent.providing_pointers());
+            assert!(TestAlloc::same_allocator(&parent, region.parent()));
+
+            region.dealloc(ptr, layout);
+            assert_eq!(0, region.allocation_count());
+            assert_eq!(0, parent.allocation_count());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn leak_in_region() {
+        let parent = GAlloc::default().with_drop_policy(DropPolicy::IgnoreOnLeak);
+        let region = parent.clone().subregion();
+        unsafe { region.alloc(Layout::new::<u64>()) };
+    }
+}
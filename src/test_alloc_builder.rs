@@ -0,0 +1,190 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use crate::{DropPolicy, TestAlloc, ViolationPolicy, ZeroSizePolicy};
+use core::alloc::GlobalAlloc;
+
+/// `TestAllocBuilder` is a builder of [`TestAlloc`] .
+///
+/// See [`TestAlloc::builder`] .
+///
+/// [`TestAlloc`]: crate::TestAlloc
+/// [`TestAlloc::builder`]: crate::TestAlloc::builder
+#[derive(Debug)]
+pub struct TestAllocBuilder<A>
+where
+    A: GlobalAlloc,
+{
+    alloc: A,
+    capacity: usize,
+    violation_policy: ViolationPolicy,
+    zero_size_policy: ZeroSizePolicy,
+    drop_policy: DropPolicy,
+    max_live: Option<usize>,
+    max_alignment_warning: Option<usize>,
+    #[cfg(feature = "backtraces")]
+    backtraces: bool,
+}
+
+impl<A> From<A> for TestAllocBuilder<A>
+where
+    A: GlobalAlloc,
+{
+    fn from(inner: A) -> Self {
+        Self {
+            alloc: inner,
+            capacity: 0,
+            violation_policy: ViolationPolicy::default(),
+            zero_size_policy: ZeroSizePolicy::default(),
+            drop_policy: DropPolicy::default(),
+            max_live: None,
+            max_alignment_warning: None,
+            #[cfg(feature = "backtraces")]
+            backtraces: false,
+        }
+    }
+}
+
+impl<A> TestAllocBuilder<A>
+where
+    A: GlobalAlloc,
+{
+    /// Sets the number of the pointers that the tracking information is pre-allocated to hold.
+    ///
+    /// See [`TestAlloc::with_capacity`] .
+    pub fn capacity(mut self, n: usize) -> Self {
+        self.capacity = n;
+        self
+    }
+
+    /// Sets whether to capture the backtrace on every allocation.
+    ///
+    /// See [`TestAlloc::with_backtraces`] .
+    #[cfg(feature = "backtraces")]
+    pub fn backtraces(mut self, enabled: bool) -> Self {
+        self.backtraces = enabled;
+        self
+    }
+
+    /// Sets the behavior on a violation.
+    pub fn violation_policy(mut self, p: ViolationPolicy) -> Self {
+        self.violation_policy = p;
+        self
+    }
+
+    /// Sets the behavior on a zero-size allocation request.
+    pub fn zero_size_policy(mut self, p: ZeroSizePolicy) -> Self {
+        self.zero_size_policy = p;
+        self
+    }
+
+    /// Sets the behavior on memory leak detected on the drop.
+    pub fn drop_policy(mut self, p: DropPolicy) -> Self {
+        self.drop_policy = p;
+        self
+    }
+
+    /// Sets the limit of the number of the live allocations.
+    ///
+    /// See [`TestAlloc::with_max_live`] .
+    pub fn max_live(mut self, n: usize) -> Self {
+        self.max_live = Some(n);
+        self
+    }
+
+    /// Sets the limit of the alignment of the allocation requests.
+    ///
+    /// See [`TestAlloc::with_max_alignment_warning`] .
+    pub fn max_alignment_warning(mut self, align: usize) -> Self {
+        self.max_alignment_warning = Some(align);
+        self
+    }
+
+    /// Creates a new `TestAlloc` .
+    pub fn build(self) -> TestAlloc<A> {
+        let mut alloc = TestAlloc::with_capacity(self.alloc, self.capacity)
+            .with_violation_policy(self.violation_policy)
+            .with_zero_size_policy(self.zero_size_policy)
+            .with_drop_policy(self.drop_policy);
+
+        if let Some(n) = self.max_live {
+            alloc = alloc.with_max_live(n);
+        }
+        if let Some(align) = self.max_alignment_warning {
+            alloc = alloc.with_max_alignment_warning(align);
+        }
+
+        #[cfg(feature = "backtraces")]
+        if self.backtraces {
+            alloc = alloc.with_backtraces();
+        }
+
+        alloc
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core::alloc::Layout;
+use std::fmt;
+
+/// `ViolationPolicy` specifies what [`TestAlloc`] does when it detects a violation.
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ViolationPolicy {
+    /// Panics immediately. This is the default.
+    #[default]
+    Panic,
+    /// Records the violation and continues.
+    /// The recorded violations are printed to the stderr when the allocator is dropped.
+    Record,
+}
+
+/// `DropPolicy` specifies what [`TestAlloc`] does when it detects memory leak on the drop.
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DropPolicy {
+    /// Treats the leak as [`Violation::Leak`] . This is the default.
+    #[default]
+    PanicOnLeak,
+    /// Prints the leak to the stderr.
+    PrintOnLeak,
+    /// Ignores the leak.
+    IgnoreOnLeak,
+}
+
+/// `ZeroSizePolicy` specifies what [`TestAlloc`] does when it is requested to allocate zero
+/// bytes.
+///
+/// The behavior of `GlobalAlloc::alloc` is undefined if the size of the layout is 0.
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ZeroSizePolicy {
+    /// Passes the request to the backend allocator. This is the default.
+    #[default]
+    Allow,
+    /// Treats the request as [`Violation::ZeroSizeAlloc`] before passing it to the backend
+    /// allocator.
+    Violation,
+}
+
+/// `Violation` represents a misuse of `GlobalAlloc` which [`TestAlloc`] detects.
+///
+/// [`TestAlloc`]: crate::TestAlloc
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Violation {
+    /// Null pointer is passed to `GlobalAlloc::dealloc` .
+    NullDealloc {
+        /// The layout passed to `dealloc` .
+        layout: Layout,
+    },
+    /// A pointer which is not allocated (or which is already deallocated) is passed to
+    /// `GlobalAlloc::dealloc` .
+    UnknownDealloc {
+        /// The pointer passed to `dealloc` .
+        ptr: *mut u8,
+        /// The layout passed to `dealloc` .
+        layout: Layout,
+    },
+    /// The layout passed to `GlobalAlloc::dealloc` is different from that passed to
+    /// `GlobalAlloc::alloc` .
+    LayoutMismatch {
+        /// The pointer passed to `dealloc` .
+        ptr: *mut u8,
+        /// The layout passed to `alloc` .
+        alloc_layout: Layout,
+        /// The layout passed to `dealloc` .
+        dealloc_layout: Layout,
+    },
+    /// The backend allocator returned a pointer which is being allocated.
+    DuplicatedAlloc {
+        /// The pointer returned from the backend allocator.
+        ptr: *mut u8,
+        /// The layout passed to `alloc` .
+        layout: Layout,
+    },
+    /// `GlobalAlloc::alloc` is requested to allocate zero bytes under
+    /// [`ZeroSizePolicy::Violation`] .
+    ZeroSizeAlloc {
+        /// The layout passed to `alloc` .
+        layout: Layout,
+    },
+    /// `GlobalAlloc::alloc` is requested alignment greater than the limit set by
+    /// `TestAlloc::with_max_alignment_warning` .
+    OverAligned {
+        /// The layout passed to `alloc` .
+        layout: Layout,
+        /// The limit of the alignment.
+        max_align: usize,
+    },
+    /// The allocator is dropped before the pointer is deallocated.
+    Leak {
+        /// The pointer which is not deallocated.
+        ptr: *mut u8,
+        /// The layout passed to `alloc` .
+        layout: Layout,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NullDealloc { .. } => {
+                f.write_str("Null pointer is passed to method GlobalAlloc.dealloc().")
+            }
+            Self::UnknownDealloc { ptr, layout } => write!(
+                f,
+                "GlobalAlloc.dealloc() is passed {:p} ({:?}), which is not allocated.",
+                ptr, layout
+            ),
+            Self::LayoutMismatch {
+                ptr,
+                alloc_layout,
+                dealloc_layout,
+            } => write!(
+                f,
+                "GlobalAlloc.dealloc() is passed a different layout from GlobalAlloc.alloc(): \
+                 {:p} is allocated with {:?} but deallocated with {:?}.",
+                ptr, alloc_layout, dealloc_layout
+            ),
+            Self::DuplicatedAlloc { ptr, layout } => write!(
+                f,
+                "GlobalAlloc.alloc() returned {:p} ({:?}), which is being allocated.",
+                ptr, layout
+            ),
+            Self::ZeroSizeAlloc { layout } => write!(
+                f,
+                "GlobalAlloc.alloc() is passed {:?}, whose size is 0.",
+                layout
+            ),
+            Self::OverAligned { layout, max_align } => write!(
+                f,
+                "GlobalAlloc.alloc() is passed {:?}, whose alignment is greater than {}.",
+                layout, max_align
+            ),
+            Self::Leak { ptr, layout } => write!(
+                f,
+                "Memory leak is detected: The allocator is dropped before {:p} ({:?}) is \
+                 deallocated.",
+                ptr, layout
+            ),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+#![cfg(feature = "allocator_api")]
+#![feature(allocator_api)]
+
+use gharial::{GAlloc, NeverAlloc, TestAlloc};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn push_and_pop() {
+    let alloc = GAlloc::default();
+    {
+        let mut vec: Vec<u32, &GAlloc> = Vec::new_in(&alloc);
+        assert_eq!(0, alloc.allocation_count());
+
+        // `Allocator::grow` allocates the new buffer before freeing the old one.
+        let mut peak_bytes = 0;
+        for i in 0..100 {
+            let capacity = vec.capacity();
+            vec.push(i);
+            assert_eq!(1, alloc.allocation_count());
+            if capacity != vec.capacity() {
+                peak_bytes = peak_bytes.max((capacity + vec.capacity()) * 4);
+            }
+        }
+
+        let stats = alloc.stats();
+        assert_eq!(2, stats.peak_live_count);
+        assert_eq!(peak_bytes, stats.peak_live_bytes);
+
+        for i in (0..100).rev() {
+            assert_eq!(Some(i), vec.pop());
+        }
+
+        vec.shrink_to_fit();
+        assert_eq!(0, alloc.allocation_count());
+    }
+    assert_eq!(0, alloc.allocation_count());
+}
+
+#[test]
+fn insert_and_remove() {
+    let alloc = GAlloc::default();
+    {
+        let mut vec: Vec<u32, &GAlloc> = Vec::with_capacity_in(4, &alloc);
+        let layout = Layout::array::<u32>(4).unwrap();
+        assert_eq!(layout, alloc.providing_pointers()[0].1);
+
+        for i in 0..4 {
+            vec.insert(0, i);
+        }
+        assert_eq!(&[3, 2, 1, 0], vec.as_slice());
+
+        // The capacity is not exceeded. No more memory is allocated.
+        assert_eq!(1, alloc.allocation_count());
+        assert_eq!(layout, alloc.providing_pointers()[0].1);
+
+        assert_eq!(2, vec.remove(1));
+        assert_eq!(&[3, 1, 0], vec.as_slice());
+
+        let stats = alloc.stats();
+        assert_eq!(1, stats.peak_live_count);
+        assert_eq!(layout.size(), stats.peak_live_bytes);
+    }
+    assert_eq!(0, alloc.allocation_count());
+}
+
+#[test]
+fn zero_sized() {
+    let alloc = GAlloc::default();
+    {
+        let mut vec: Vec<(), &GAlloc> = Vec::new_in(&alloc);
+        for _ in 0..100 {
+            vec.push(());
+        }
+        assert_eq!(0, alloc.allocation_count());
+    }
+}
+
+#[test]
+fn try_alloc_layout() {
+    let alloc = GAlloc::default();
+    let layout = Layout::new::<u64>();
+
+    let ptr = alloc.try_alloc_layout(layout).unwrap();
+    assert_eq!(8, ptr.len());
+    assert_eq!(1, alloc.allocation_count());
+    unsafe { alloc.deallocate(ptr.cast(), layout) };
+
+    let ptr = alloc.try_alloc_layout(Layout::new::<()>()).unwrap();
+    assert_eq!(0, ptr.len());
+    assert_eq!(0, alloc.allocation_count());
+
+    let alloc = TestAlloc::from(NeverAlloc);
+    assert!(alloc.try_alloc_layout(layout).is_err());
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 Shin Yoshida
+//
+// "LGPL-3.0-or-later OR Apache-2.0 OR BSD-2-Clause OR MIT"
+//
+// This is part of test-allocator
+//
+//  test-allocator is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+//
+//  test-allocator is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public License
+//  along with test-allocator.  If not, see <http://www.gnu.org/licenses/>.
+//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted
+// provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of
+//    conditions and the following disclaimer.
+// 2. Redistributions in binary form must reproduce the above copyright notice, this
+//    list of conditions and the following disclaimer in the documentation and/or other
+//    materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND
+// ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+// IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT,
+// INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT
+// NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice (including the next paragraph) shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Every function which allocates memory returns null or `Err` rather than crashing when the
+//! backend allocator fails. `NeverAlloc::dealloc` panics, so these tests also confirm that nothing
+//! is deallocated after the failure.
+
+use gharial::{
+    AllocStats, MaybeAlloc, NeverAlloc, RoundRobinAlloc, SerialAlloc, ShardedTestAlloc, TestAlloc,
+    TestBox,
+};
+use std::alloc::{GlobalAlloc, Layout};
+
+fn assert_no_state<A: GlobalAlloc>(alloc: &TestAlloc<A>) {
+    assert_eq!(Ok(()), alloc.check_leaks());
+    assert_eq!(0, alloc.allocation_count());
+    assert_eq!(0, alloc.allocation_bytes());
+    assert_eq!(AllocStats::default(), alloc.stats());
+    assert!(alloc.history().is_empty());
+    assert!(!alloc.has_violations());
+}
+
+#[test]
+fn test_box_try_new() {
+    let alloc = TestAlloc::from(NeverAlloc);
+
+    let s = TestBox::try_new("foo".to_string(), alloc.clone()).unwrap_err();
+    assert_eq!("foo", s);
+    assert_no_state(&alloc);
+}
+
+#[test]
+fn test_alloc() {
+    let alloc = TestAlloc::from(NeverAlloc);
+    let layout = Layout::new::<u64>();
+
+    unsafe {
+        assert!(alloc.alloc(layout).is_null());
+        assert!(alloc.alloc_zeroed(layout).is_null());
+        assert!(alloc.alloc_tagged(layout, 1).is_null());
+    }
+    assert!(alloc.allocations_with_tag(1).is_empty());
+    assert_no_state(&alloc);
+}
+
+#[test]
+fn wrappers() {
+    let layout = Layout::new::<u64>();
+
+    unsafe {
+        let alloc = MaybeAlloc::from(TestAlloc::from(NeverAlloc));
+        for _ in 0..64 {
+            assert!(alloc.alloc(layout).is_null());
+        }
+        assert_no_state(alloc.inner_test_alloc());
+
+        let alloc = SerialAlloc::from(NeverAlloc);
+        assert!(alloc.alloc(layout).is_null());
+
+        let alloc = RoundRobinAlloc::new(NeverAlloc, NeverAlloc);
+        assert!(alloc.alloc(layout).is_null());
+        assert!(alloc.alloc(layout).is_null());
+
+        let alloc = ShardedTestAlloc::<NeverAlloc, 4>::from(NeverAlloc);
+        assert!(alloc.alloc(layout).is_null());
+        assert_eq!(0, alloc.allocation_count());
+    }
+}